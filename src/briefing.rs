@@ -0,0 +1,167 @@
+use crate::config::get_list;
+use crate::grammar::{parse_term, Term, Value};
+use crate::notify::{Channel, Notification};
+use crate::reminder::{due_between, Reminder};
+use crate::schedule::Schedule;
+use crate::Result;
+use chrono::prelude::*;
+use chrono::{NaiveDate, NaiveDateTime};
+use yaml_rust::Yaml;
+
+/* The ledger identity of the briefing (there's only one a day). */
+pub const BRIEFING_ID: &str = "briefing";
+
+/*
+ * The daily briefing, one notification listing the reminders for the rest
+ * of the day and any overdue tasks. Sent to the named channels or, if none
+ * are named, every channel that isn't `fallback_only`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Briefing {
+    pub schedule: Schedule,
+    pub channels: Vec<String>,
+}
+
+impl Briefing {
+    /*
+     * Parse the `briefing:` config, either the time of day (e.g. "7:30") or
+     * a mapping with `time:` and `channels:`. Returns None if there's no
+     * briefing.
+     */
+    pub fn from_config(cfg: &Yaml) -> Result<Option<Briefing>> {
+        let b = &cfg["briefing"];
+        let (time, channels) = match b {
+            Yaml::BadValue | Yaml::Null => return Ok(None),
+            Yaml::Boolean(false) => return Ok(None),
+            Yaml::String(s) => (s.as_str(), Vec::new()),
+            Yaml::Hash(_) => match b["time"].as_str() {
+                Some(t) => (t, get_list(b, "channels")),
+                None => return Err("briefing requires 'time'".into()),
+            },
+            _ => return Err("briefing must be a time or a mapping".into()),
+        };
+
+        let time = match parse_term(Term::Time, time) {
+            Some(Ok(Value::Time(t))) => t,
+            Some(Err(e)) => return Err(format!("invalid briefing time ({})", e).into()),
+            _ => return Err(format!("invalid briefing time '{}'", time).into()),
+        };
+
+        Ok(Some(Briefing {
+            schedule: Schedule::Daily { time },
+            channels,
+        }))
+    }
+
+    /* The channels the briefing is sent to. */
+    pub fn recipients<'a>(&self, channels: &'a [Channel]) -> Vec<&'a Channel> {
+        channels
+            .iter()
+            .filter(|ch| !ch.fallback_only)
+            .filter(|ch| self.channels.is_empty() || self.channels.contains(&ch.name))
+            .collect()
+    }
+
+    /*
+     * Build the briefing for the occurrence at, listing the reminders due
+     * between now and the end of the day then the overdue tasks.
+     */
+    pub fn notification(
+        &self,
+        reminders: &[Reminder],
+        at: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> Notification {
+        let mut lines: Vec<String> = due_between(reminders, now, now.date().and_hms(23, 59, 0))
+            .iter()
+            .map(|o| format!("- {} {}", o.at.format("%H:%M"), o.reminder.text))
+            .collect();
+        if lines.is_empty() {
+            lines.push("Nothing else today".to_string());
+        }
+
+        let overdue = overdue_tasks(reminders, now);
+        if !overdue.is_empty() {
+            lines.push("Overdue:".to_string());
+            for (d, r) in overdue {
+                lines.push(format!("- {} {}", d.format("%Y/%m/%d"), r.text));
+            }
+        }
+
+        let mut n = Notification::new(&lines.join("\n"), at);
+        n.title = Some(format!("Agenda for {}", now.format("%a %Y/%m/%d")));
+        n.late = at < now;
+        n
+    }
+}
+
+/*
+ * The unchecked tasks whose date and time have passed, oldest first. A date
+ * without a year is taken as this year's. Tasks on a repeating schedule are
+ * never overdue.
+ */
+pub fn overdue_tasks(reminders: &[Reminder], now: NaiveDateTime) -> Vec<(NaiveDate, &Reminder)> {
+    let mut v: Vec<(NaiveDate, &Reminder)> = reminders
+        .iter()
+        .filter(|r| r.task)
+        .filter_map(|r| match r.schedule {
+            Schedule::Date {
+                year,
+                month,
+                day,
+                time,
+            } => NaiveDate::from_ymd_opt(year.unwrap_or_else(|| now.year()), month, day)
+                .filter(|d| d.and_time(time) < now)
+                .map(|d| (d, r)),
+            _ => None,
+        })
+        .collect();
+
+    v.sort_by_key(|(d, _)| *d);
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    fn texts(v: Vec<(NaiveDate, &Reminder)>) -> Vec<&str> {
+        v.iter().map(|(_, r)| r.text.as_str()).collect()
+    }
+
+    #[test]
+    fn overdue_tasks_by_time() {
+        let reminders: Vec<Reminder> = [
+            "- [ ] later /remind 4/29/2020 3pm",
+            "- [ ] earlier today /remind 4/29/2020 9am",
+            "- [ ] yesterday /remind 4/28/2020 11pm",
+            "- [ ] tomorrow /remind 4/30/2020 9am",
+            "- [ ] in march /remind 3/1 9am",
+            "- [ ] weekly /remind mon 9am",
+            "/remind 4/28/2020 9am not a task",
+        ]
+        .iter()
+        .map(|l| Reminder::parse(l).unwrap().unwrap())
+        .collect();
+
+        let overdue = overdue_tasks(&reminders, dt(2020, 4, 29, 11, 0));
+        assert_eq!(
+            overdue[0].0,
+            NaiveDate::from_ymd(2020, 3, 1),
+            "a date without a year is this year's"
+        );
+        assert_eq!(
+            texts(overdue),
+            vec!["in march", "yesterday", "earlier today"]
+        );
+
+        assert_eq!(
+            texts(overdue_tasks(&reminders, dt(2020, 4, 29, 9, 0))),
+            vec!["in march", "yesterday"]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use crate::Result;
+use std::fs;
+use yaml_rust::{Yaml, YamlLoader};
+
+/* Read in the YAML config file and parse it, returning the first document. */
+pub fn load_config(file: &str) -> Result<Yaml> {
+    let s = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("failed to read config file {} ({})", file, e).into()),
+    };
+
+    let mut docs = YamlLoader::load_from_str(&s)?;
+    if docs.is_empty() {
+        return Err(format!("empty config file {}", file).into());
+    }
+
+    Ok(docs.swap_remove(0))
+}
+
+/* Return a config variable as a string, treating a missing or null value as None. */
+pub fn get_str<'a>(cfg: &'a Yaml, key: &str) -> Option<&'a str> {
+    let v = &cfg[key];
+    if v.is_badvalue() || v.is_null() {
+        return None;
+    }
+
+    v.as_str()
+}
+
+/* Return a config variable as an integer, treating a missing or null value as None. */
+pub fn get_int(cfg: &Yaml, key: &str) -> Option<i64> {
+    cfg[key].as_i64()
+}
+
+/* Return a config variable as a boolean, treating a missing or null value as None. */
+pub fn get_bool(cfg: &Yaml, key: &str) -> Option<bool> {
+    cfg[key].as_bool()
+}
+
+/*
+ * Return a config variable as a list of strings. The value can either be a
+ * YAML list or a single comma separated string.
+ */
+pub fn get_list(cfg: &Yaml, key: &str) -> Vec<String> {
+    match &cfg[key] {
+        Yaml::Array(a) => a
+            .iter()
+            .filter_map(|v| match v {
+                Yaml::String(s) => Some(s.clone()),
+                Yaml::Integer(i) => Some(i.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Yaml::String(s) => s
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,217 @@
+use crate::briefing::Briefing;
+use crate::config::{get_int, get_str, load_config};
+use crate::ledger::{open_ledger, Ledger};
+use crate::notify::{load_channels, Channel};
+use crate::reminder::Reminder;
+use crate::report::Report;
+use crate::run::{brief, deliver, evaluated_until, follow_up};
+use crate::source::{load_reminders, load_sources, parse_sources};
+use crate::state::{catchup_from, catchup_max, catchup_start, save_state, DEFAULT_CATCHUP_MAX};
+use crate::Result;
+use chrono::prelude::*;
+use chrono::{Duration, NaiveDateTime};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use std::{thread, time};
+use yaml_rust::Yaml;
+
+/* The default interval (in seconds) between re-fetches of an HTTP `file:`. */
+pub const DEFAULT_POLL_INTERVAL: i64 = 300;
+
+/* How often the daemon wakes to check for signals and file changes. */
+const TICK: time::Duration = time::Duration::from_secs(1);
+
+/* The current time truncated to the minute. */
+pub fn now_minute() -> NaiveDateTime {
+    Local::now()
+        .naive_local()
+        .with_nanosecond(0)
+        .unwrap()
+        .with_second(0)
+        .unwrap()
+}
+
+fn file_mtime(file: &str) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/* The loaded config and reminders along with what's needed to spot changes. */
+struct Loaded {
+    cfg: Yaml,
+    channels: Vec<Channel>,
+    briefing: Option<Briefing>,
+    reminders: Vec<Reminder>,
+    mtime: Option<SystemTime>,
+    fetched: Instant,
+}
+
+impl Loaded {
+    /* Load the channels and reminders for the config. */
+    fn load(cfg: Yaml, strict: bool) -> Result<Loaded> {
+        let mut loaded = Loaded {
+            channels: load_channels(&cfg)?,
+            briefing: Briefing::from_config(&cfg)?,
+            cfg,
+            reminders: Vec::new(),
+            mtime: None,
+            fetched: Instant::now(),
+        };
+        loaded.reload(strict)?;
+        Ok(loaded)
+    }
+
+    /*
+     * Load the reminders from the sources, keeping the channels (and so
+     * their rate limits and other state). A source that fails to load is
+     * reported and skipped unless strict, when it's an error instead (i.e.
+     * on a reload the old reminders are kept rather than losing those from
+     * a file that's briefly unreachable).
+     */
+    fn reload(&mut self, strict: bool) -> Result<()> {
+        let mtime = match get_str(&self.cfg, "file") {
+            Some(f) if !f.starts_with("http") => file_mtime(f),
+            _ => None,
+        };
+
+        let mut report = Report::new();
+        let reminders = match strict {
+            true => parse_sources(&load_sources(&self.cfg)?, &mut report),
+            false => load_reminders(&self.cfg, &mut report)?,
+        };
+        println!("loaded {} reminders", reminders.len());
+        report.print();
+
+        self.reminders = reminders;
+        self.mtime = mtime;
+        self.fetched = Instant::now();
+        Ok(())
+    }
+
+    /*
+     * Return true if the sources should be reloaded. A local file is
+     * reloaded when it changes, an HTTP file every `poll_interval:` seconds.
+     */
+    fn stale(&self) -> bool {
+        match get_str(&self.cfg, "file") {
+            Some(f) if f.starts_with("http") => {
+                let secs = get_int(&self.cfg, "poll_interval").unwrap_or(DEFAULT_POLL_INTERVAL);
+                self.fetched.elapsed().as_secs() >= secs.max(1) as u64
+            }
+            Some(f) => file_mtime(f) != self.mtime,
+            None => false,
+        }
+    }
+
+    /* The next time any reminder (or the briefing) fires after the given time. */
+    fn next_due(&self, dt: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.reminders
+            .iter()
+            .map(|r| &r.schedule)
+            .chain(self.briefing.as_ref().map(|b| &b.schedule))
+            .filter_map(|s| s.next_after(dt))
+            .min()
+    }
+}
+
+/*
+ * Stay resident and deliver reminders as they come due. The reminders are
+ * parsed once and the daemon sleeps until the next occurrence. Sources are
+ * reloaded when they change and the config file (and with it the channels)
+ * is reloaded on SIGHUP. The ledger is only locked and loaded while
+ * delivering so other runs aren't held up, and with one a failed delivery
+ * is retried every minute.
+ */
+pub fn daemon(cfg_file: &str, force: bool) -> Result<()> {
+    let hup = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&hup))?;
+
+    let mut loaded = Loaded::load(load_config(cfg_file)?, false)?;
+
+    let now = now_minute();
+    open_ledger(&loaded.cfg, now, force)?;
+
+    /* the last minute evaluated and the last minute the loop ran */
+    let mut last = catchup_from(&loaded.cfg, now)? - Duration::minutes(1);
+    let mut ran = last;
+    let mut next = Some(last);
+
+    loop {
+        if hup.swap(false, Ordering::Relaxed) {
+            println!("SIGHUP, reloading {}", cfg_file);
+            match load_config(cfg_file).and_then(|cfg| Loaded::load(cfg, true)) {
+                Ok(l) => loaded = l,
+                Err(e) => eprintln!("reload failed ({}), keeping the old config", e),
+            }
+            next = loaded.next_due(last);
+        } else if loaded.stale() {
+            if let Err(e) = loaded.reload(true) {
+                eprintln!("reload failed ({}), keeping the old reminders", e);
+                loaded.mtime = get_str(&loaded.cfg, "file").and_then(file_mtime);
+                loaded.fetched = Instant::now();
+            }
+            next = loaded.next_due(last);
+        }
+
+        /* nothing fires between the last minute evaluated and the next due */
+        let now = now_minute();
+        if now > ran {
+            let mut report = Report::new();
+            if next.is_some_and(|n| n <= now) {
+                let mut ledger: Option<Ledger> = open_ledger(&loaded.cfg, now, force)
+                    .unwrap_or_else(|e| {
+                        report.add("ledger", e);
+                        None
+                    });
+                /* a failure is only retried for as long as `catchup_max:` */
+                let max = catchup_max(&loaded.cfg).unwrap_or(DEFAULT_CATCHUP_MAX);
+                let from = catchup_start(Some(last), now, max);
+                let briefed = match &loaded.briefing {
+                    Some(b) => brief(
+                        b,
+                        &loaded.channels,
+                        &loaded.reminders,
+                        from,
+                        now,
+                        &mut ledger,
+                        &mut report,
+                    ),
+                    None => None,
+                };
+                let failed = deliver(
+                    &loaded.cfg,
+                    &loaded.channels,
+                    &loaded.reminders,
+                    from,
+                    now,
+                    &mut ledger,
+                    &mut report,
+                );
+                last = evaluated_until(now, briefed.into_iter().chain(failed).min(), &ledger);
+                next = match last < now {
+                    true => Some(now + Duration::minutes(1)),
+                    false => loaded.next_due(now),
+                };
+            } else {
+                last = now;
+            }
+            follow_up(&loaded.channels, &mut report);
+            if let Err(e) = save_state(&loaded.cfg, last) {
+                report.add("state", e);
+            }
+            report.print();
+            ran = now;
+        }
+
+        /* sleep until the next occurrence, waking to check for changes */
+        let wait = match next {
+            Some(n) => (n - Local::now().naive_local())
+                .to_std()
+                .unwrap_or(TICK)
+                .min(TICK),
+            None => TICK,
+        };
+        thread::sleep(wait);
+    }
+}
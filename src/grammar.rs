@@ -0,0 +1,416 @@
+/*
+ * The schedule grammar. A schedule specification is a short list of words
+ * (e.g. "4/29/2020 11:00am" or "tue 10pm") and each rule below describes one
+ * valid sequence of terms along with how to build a Schedule from them. To
+ * support a new date form add a rule to RULES (and a term if needed).
+ */
+
+use crate::schedule::{default_time, Schedule};
+use crate::Result;
+use chrono::{NaiveDate, NaiveTime, Weekday};
+use regex::Regex;
+
+lazy_static! {
+    /* <MM>/<DD>[/<YY>|/<YYYY>] (ASCII digits only, \d is any Unicode digit) */
+    static ref DATE: Regex = Regex::new(
+        r"^(?P<month>[0-9]{1,2})/(?P<day>[0-9]{1,2})(/(?P<year>[0-9]{2}|[0-9]{4}))?$"
+    ).unwrap();
+
+    /* <HH>:<MM><am|pm> | <HH>:<MM> | <HH><am|pm> */
+    static ref TIME: Regex =
+        Regex::new(r"^(?P<hour>[0-9]{1,2})(:(?P<min>[0-9]{2}))?(?P<ampm>am|pm)?$").unwrap();
+}
+
+/* The kinds of terms a rule is made of. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Term {
+    Date,
+    Time,
+    Weekday,
+    Word(&'static str),
+}
+
+/* The value of a term parsed from a word. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Value {
+    Date {
+        year: Option<i32>,
+        month: u32,
+        day: u32,
+    },
+    Time(NaiveTime),
+    Weekday(Weekday),
+    Word,
+}
+
+/* A sequence of terms and how to turn their values into a Schedule. */
+pub struct Rule {
+    pub terms: &'static [Term],
+    pub build: fn(&[Value]) -> Schedule,
+}
+
+fn date(v: &Value) -> (Option<i32>, u32, u32) {
+    match *v {
+        Value::Date { year, month, day } => (year, month, day),
+        _ => unreachable!(),
+    }
+}
+
+fn time(v: &Value) -> NaiveTime {
+    match *v {
+        Value::Time(t) => t,
+        _ => unreachable!(),
+    }
+}
+
+fn weekday(v: &Value) -> Weekday {
+    match *v {
+        Value::Weekday(wd) => wd,
+        _ => unreachable!(),
+    }
+}
+
+fn date_at(v: &Value, time: NaiveTime) -> Schedule {
+    let (year, month, day) = date(v);
+    Schedule::Date {
+        year,
+        month,
+        day,
+        time,
+    }
+}
+
+/* Checked in order, the first rule that matches wins. */
+pub static RULES: &[Rule] = &[
+    /* <MM>/<DD>[/<YY>|/<YYYY>] <time> - Date @ <time> */
+    Rule {
+        terms: &[Term::Date, Term::Time],
+        build: |v| date_at(&v[0], time(&v[1])),
+    },
+    /* <MM>/<DD>[/<YY>|/<YYYY>] - Date @ 8:00am */
+    Rule {
+        terms: &[Term::Date],
+        build: |v| date_at(&v[0], default_time()),
+    },
+    /* <sun|mon|tue|wed|thu|fri|sat> <time> - <weekday> @ <time> */
+    Rule {
+        terms: &[Term::Weekday, Term::Time],
+        build: |v| Schedule::Weekly {
+            weekday: weekday(&v[0]),
+            time: time(&v[1]),
+        },
+    },
+    /* <sun|mon|tue|wed|thu|fri|sat> - <weekday> @ 8:00am */
+    Rule {
+        terms: &[Term::Weekday],
+        build: |v| Schedule::Weekly {
+            weekday: weekday(&v[0]),
+            time: default_time(),
+        },
+    },
+    /* <time> - Every day @ <time> */
+    Rule {
+        terms: &[Term::Time],
+        build: |v| Schedule::Daily { time: time(&v[0]) },
+    },
+    /* monthly - Every month on the 1st @ 8:00am */
+    Rule {
+        terms: &[Term::Word("monthly")],
+        build: |_| Schedule::Monthly {
+            time: default_time(),
+        },
+    },
+    /* biweekly - Every other Monday @ 8:00am (even weeks) */
+    Rule {
+        terms: &[Term::Word("biweekly")],
+        build: |_| Schedule::Biweekly {
+            weekday: Weekday::Mon,
+            time: default_time(),
+        },
+    },
+    /* weekly - Every Monday @ 8:00am */
+    Rule {
+        terms: &[Term::Word("weekly")],
+        build: |_| Schedule::Weekly {
+            weekday: Weekday::Mon,
+            time: default_time(),
+        },
+    },
+    /* daily - Every day @ 8:00am */
+    Rule {
+        terms: &[Term::Word("daily")],
+        build: |_| Schedule::Daily {
+            time: default_time(),
+        },
+    },
+];
+
+fn parse_date(word: &str) -> Option<Result<Value>> {
+    let c = DATE.captures(word)?;
+    let invalid = || Some(Err(format!("invalid date '{}'", word).into()));
+
+    let (month, day) = match (c["month"].parse::<u32>(), c["day"].parse::<u32>()) {
+        (Ok(m), Ok(d)) => (m, d),
+        _ => return invalid(),
+    };
+    let year = match c.name("year").map(|y| y.as_str().parse::<i32>()) {
+        None => None,
+        Some(Ok(y)) if y <= 99 => Some(y + 2000),
+        Some(Ok(y)) => Some(y),
+        Some(Err(_)) => return invalid(),
+    };
+
+    /* without a year the date must exist in a leap year (i.e. allow 2/29) */
+    if NaiveDate::from_ymd_opt(year.unwrap_or(2000), month, day).is_none() {
+        return invalid();
+    }
+
+    Some(Ok(Value::Date { year, month, day }))
+}
+
+fn parse_time(word: &str) -> Option<Result<Value>> {
+    let c = TIME.captures(word)?;
+
+    /* a bare number isn't a time */
+    if c.name("min").is_none() && c.name("ampm").is_none() {
+        return None;
+    }
+
+    let invalid = || Some(Err(format!("invalid time '{}'", word).into()));
+
+    let mut hour = match c["hour"].parse::<u32>() {
+        Ok(h) => h,
+        Err(_) => return invalid(),
+    };
+    let min = match c.name("min").map(|m| m.as_str().parse::<u32>()) {
+        None => 0,
+        Some(Ok(m)) => m,
+        Some(Err(_)) => return invalid(),
+    };
+
+    if let Some(ampm) = c.name("ampm") {
+        if !(1..=12).contains(&hour) {
+            return invalid();
+        }
+        hour %= 12; /* 12am is midnight */
+        if ampm.as_str() == "pm" {
+            hour += 12;
+        }
+    }
+
+    match NaiveTime::from_hms_opt(hour, min, 0) {
+        Some(t) => Some(Ok(Value::Time(t))),
+        None => invalid(),
+    }
+}
+
+fn parse_weekday(word: &str) -> Option<Result<Value>> {
+    let wd = match word {
+        "sun" => Weekday::Sun,
+        "mon" => Weekday::Mon,
+        "tue" => Weekday::Tue,
+        "wed" => Weekday::Wed,
+        "thu" => Weekday::Thu,
+        "fri" => Weekday::Fri,
+        "sat" => Weekday::Sat,
+        _ => return None,
+    };
+
+    Some(Ok(Value::Weekday(wd)))
+}
+
+/*
+ * Parse a word as the given term. Returns None if the word isn't that kind
+ * of term and an error if it is but the value is out of range.
+ */
+pub fn parse_term(term: Term, word: &str) -> Option<Result<Value>> {
+    let word = word.to_lowercase();
+
+    match term {
+        Term::Date => parse_date(&word),
+        Term::Time => parse_time(&word),
+        Term::Weekday => parse_weekday(&word),
+        Term::Word(w) if w == word => Some(Ok(Value::Word)),
+        Term::Word(_) => None,
+    }
+}
+
+/* Match a rule against the leading words, None if the rule doesn't match. */
+fn match_rule(rule: &Rule, words: &[&str]) -> Result<Option<Schedule>> {
+    if words.len() < rule.terms.len() {
+        return Ok(None);
+    }
+
+    let mut values = Vec::with_capacity(rule.terms.len());
+    for (term, word) in rule.terms.iter().zip(words) {
+        match parse_term(*term, word) {
+            None => return Ok(None),
+            Some(v) => values.push(v?),
+        }
+    }
+
+    Ok(Some((rule.build)(&values)))
+}
+
+/*
+ * Parse the schedule at the start of the words. If `exact` is set then all
+ * the words must be used. Returns the schedule and the number of words used.
+ * When not exact and all the words are a schedule (e.g. "mon 9am") the text
+ * is missing, rather than a shorter schedule followed by the text "9am".
+ */
+pub fn parse_schedule(words: &[&str], exact: bool) -> Result<(Schedule, usize)> {
+    if !exact && !words.is_empty() && parse_schedule(words, true).is_ok() {
+        return Err("missing reminder text".into());
+    }
+
+    for rule in RULES {
+        let n = rule.terms.len();
+        if (exact && words.len() != n) || (!exact && words.len() <= n) {
+            continue;
+        }
+
+        if let Some(s) = match_rule(rule, words)? {
+            return Ok((s, n));
+        }
+    }
+
+    if words.is_empty() {
+        return Err("missing schedule".into());
+    }
+
+    Err(format!("unrecognized schedule '{}'", words.join(" ")).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::Reminder;
+
+    fn hm(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms(h, m, 0)
+    }
+
+    fn schedule(spec: &str) -> Result<(Schedule, usize)> {
+        let words: Vec<&str> = spec.split_whitespace().collect();
+        parse_schedule(&words, false)
+    }
+
+    fn err(spec: &str) -> String {
+        schedule(spec).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn noon_and_midnight() {
+        assert_eq!(
+            schedule("12am x").unwrap(),
+            (Schedule::Daily { time: hm(0, 0) }, 1)
+        );
+        assert_eq!(
+            schedule("12pm x").unwrap(),
+            (Schedule::Daily { time: hm(12, 0) }, 1)
+        );
+        assert_eq!(
+            schedule("12:30am x").unwrap().0,
+            Schedule::Daily { time: hm(0, 30) }
+        );
+        assert_eq!(
+            schedule("13:00 x").unwrap().0,
+            Schedule::Daily { time: hm(13, 0) }
+        );
+    }
+
+    #[test]
+    fn invalid_times() {
+        assert_eq!(err("0am x"), "invalid time '0am'");
+        assert_eq!(err("13pm x"), "invalid time '13pm'");
+        assert_eq!(err("24:00 x"), "invalid time '24:00'");
+        assert_eq!(err("9:60 x"), "invalid time '9:60'");
+    }
+
+    #[test]
+    fn non_ascii_digits() {
+        assert_eq!(err("\u{663}pm x"), "unrecognized schedule '\u{663}pm x'");
+        assert_eq!(
+            err("4/\u{662}\u{669} x"),
+            "unrecognized schedule '4/\u{662}\u{669} x'"
+        );
+        assert!(Reminder::parse("/remind \u{663}pm x").is_err());
+        assert!(Reminder::parse("- [ ] x /remind \u{663}:\u{660}\u{660}").is_err());
+    }
+
+    #[test]
+    fn leap_day() {
+        let every = Schedule::Date {
+            year: None,
+            month: 2,
+            day: 29,
+            time: default_time(),
+        };
+        assert_eq!(schedule("2/29 x").unwrap(), (every, 1));
+        assert!(schedule("2/29/2020 x").is_ok());
+        assert_eq!(err("2/29/2021 x"), "invalid date '2/29/2021'");
+        assert_eq!(err("2/30 x"), "invalid date '2/30'");
+    }
+
+    #[test]
+    fn years() {
+        let date = Schedule::Date {
+            year: Some(2020),
+            month: 4,
+            day: 29,
+            time: hm(11, 0),
+        };
+        assert_eq!(schedule("4/29/20 11am x").unwrap(), (date, 2));
+        assert_eq!(schedule("4/29/2020 11am x").unwrap(), (date, 2));
+        assert_eq!(schedule("04/29/2020 11:00 x").unwrap(), (date, 2));
+        assert!(schedule("4/29/202 x").is_err());
+    }
+
+    #[test]
+    fn missing_text() {
+        assert_eq!(err("4/29 11:00am"), "missing reminder text");
+        assert_eq!(err("mon 9am"), "missing reminder text");
+        assert_eq!(err("daily"), "missing reminder text");
+        assert_eq!(err(""), "missing schedule");
+        assert_eq!(err("someday x"), "unrecognized schedule 'someday x'");
+        assert_eq!(
+            schedule("mon 9am standup").unwrap(),
+            (
+                Schedule::Weekly {
+                    weekday: Weekday::Mon,
+                    time: hm(9, 0),
+                },
+                2
+            )
+        );
+    }
+
+    #[test]
+    fn plain_and_task_lines() {
+        let r = Reminder::parse("/remind tue 10pm take out the trash")
+            .unwrap()
+            .unwrap();
+        assert_eq!(r.text, "take out the trash");
+        assert!(!r.task);
+        assert_eq!(
+            r.schedule,
+            Schedule::Weekly {
+                weekday: Weekday::Tue,
+                time: hm(22, 0),
+            }
+        );
+
+        let r = Reminder::parse("- [ ] take out the trash /remind tue 10pm")
+            .unwrap()
+            .unwrap();
+        assert_eq!(r.text, "take out the trash");
+        assert!(r.task);
+        assert_eq!(r.schedule.time(), hm(22, 0));
+
+        /* a task's schedule must use all the words */
+        assert!(Reminder::parse("- [ ] trash /remind tue 10pm now").is_err());
+        assert!(Reminder::parse("- [ ] trash /remind").is_err());
+        assert_eq!(Reminder::parse("- [x] trash /remind tue").unwrap(), None);
+        assert_eq!(Reminder::parse("just some text").unwrap(), None);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::config::get_str;
+use crate::state::{catchup_max, MAX_CATCHUP_MAX};
+use crate::Result;
+use chrono::{Duration, NaiveDateTime};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use yaml_rust::Yaml;
+
+const LEDGER_FMT: &str = "%Y/%m/%d %H:%M";
+
+/* How long delivered notifications are remembered (at least). */
+pub const LEDGER_KEEP_DAYS: i64 = 7;
+
+/*
+ * A record of every notification delivered so the same reminder occurrence
+ * is never sent to the same channel twice. Each line in the ledger file is
+ * "<occurrence time>\t<channel>\t<reminder identity>". The ledger holds an
+ * exclusive lock on "<ledger file>.lock" from when it's loaded until it's
+ * dropped so two runs at once can't both deliver the same occurrence.
+ */
+#[derive(Debug, Default)]
+pub struct Ledger {
+    file: String,
+    entries: HashSet<(NaiveDateTime, String, String)>,
+    _lock: Option<File>,
+}
+
+/*
+ * How long the ledger remembers deliveries for the config. A failed delivery
+ * is retried for up to `catchup_max:` so the channels that did get it must
+ * remember it for at least that long or they'd be sent it again.
+ */
+pub fn retention(cfg: &Yaml) -> Duration {
+    let max = catchup_max(cfg).unwrap_or(MAX_CATCHUP_MAX);
+    Duration::days(LEDGER_KEEP_DAYS).max(Duration::minutes(max))
+}
+
+/* Load the `ledger_file:` in the config, None if there isn't one or forced. */
+pub fn open_ledger(cfg: &Yaml, now: NaiveDateTime, force: bool) -> Result<Option<Ledger>> {
+    match get_str(cfg, "ledger_file") {
+        Some(f) if !force => Ok(Some(Ledger::load(f, now, retention(cfg))?)),
+        _ => Ok(None),
+    }
+}
+
+/* Open the ledger's lock file and wait for an exclusive lock on it. */
+fn lock(file: &str) -> Result<File> {
+    let path = format!("{}.lock", file);
+    let rc = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .and_then(|f| f.lock().map(|_| f));
+
+    match rc {
+        Ok(f) => Ok(f),
+        Err(e) => Err(format!("failed to lock ledger file {} ({})", path, e).into()),
+    }
+}
+
+fn clean(s: &str) -> String {
+    s.replace('\t', " ")
+}
+
+impl Ledger {
+    /*
+     * Lock and load the ledger file, forgetting entries older than
+     * keep before now. A missing file is an empty ledger.
+     */
+    pub fn load(file: &str, now: NaiveDateTime, keep: Duration) -> Result<Ledger> {
+        let mut ledger = Ledger {
+            file: file.to_string(),
+            entries: HashSet::new(),
+            _lock: Some(lock(file)?),
+        };
+
+        let s = match fs::read_to_string(file) {
+            Ok(s) => s,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(ledger),
+            Err(e) => return Err(format!("failed to read ledger file {} ({})", file, e).into()),
+        };
+
+        let oldest = now - keep;
+        let mut pruned = false;
+
+        for line in s.lines() {
+            let mut f = line.splitn(3, '\t');
+            let (at, channel, id) = match (f.next(), f.next(), f.next()) {
+                (Some(a), Some(c), Some(i)) => (a, c, i),
+                _ => continue,
+            };
+
+            let at = match NaiveDateTime::parse_from_str(at, LEDGER_FMT) {
+                Ok(at) => at,
+                Err(_) => continue,
+            };
+
+            if at < oldest {
+                pruned = true;
+                continue;
+            }
+
+            ledger
+                .entries
+                .insert((at, channel.to_string(), id.to_string()));
+        }
+
+        if pruned {
+            ledger.save()?;
+        }
+
+        Ok(ledger)
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort();
+
+        let s: String = entries
+            .iter()
+            .map(|(at, channel, id)| format!("{}\t{}\t{}\n", at.format(LEDGER_FMT), channel, id))
+            .collect();
+
+        if let Err(e) = fs::write(&self.file, s) {
+            return Err(format!("failed to write ledger file {} ({})", self.file, e).into());
+        }
+
+        Ok(())
+    }
+
+    /* Return true if the occurrence has already been delivered to the channel. */
+    pub fn contains(&self, id: &str, at: NaiveDateTime, channel: &str) -> bool {
+        self.entries.contains(&(at, clean(channel), clean(id)))
+    }
+
+    /* Record a delivered occurrence, appending it to the ledger file. */
+    pub fn record(&mut self, id: &str, at: NaiveDateTime, channel: &str) -> Result<()> {
+        let (channel, id) = (clean(channel), clean(id));
+
+        let line = format!("{}\t{}\t{}\n", at.format(LEDGER_FMT), channel, id);
+        let rc = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)
+            .and_then(|mut f| f.write_all(line.as_bytes()));
+        if let Err(e) = rc {
+            return Err(format!("failed to write ledger file {} ({})", self.file, e).into());
+        }
+
+        self.entries.insert((at, channel, id));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::env;
+    use std::process;
+    use yaml_rust::YamlLoader;
+
+    fn dt(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    /* A ledger file unique to the test, removed along with its lock file. */
+    fn ledger_file(name: &str) -> String {
+        let f = env::temp_dir().join(format!("reminders-{}-{}.ledger", name, process::id()));
+        let f = f.to_string_lossy().to_string();
+        let _ = fs::remove_file(&f);
+        f
+    }
+
+    fn remove(file: &str) {
+        let _ = fs::remove_file(file);
+        let _ = fs::remove_file(format!("{}.lock", file));
+    }
+
+    #[test]
+    fn record_and_reload() {
+        let file = ledger_file("record");
+        let now = dt(2020, 4, 29, 11, 0);
+        let keep = Duration::days(LEDGER_KEEP_DAYS);
+
+        let mut ledger = Ledger::load(&file, now, keep).unwrap();
+        assert!(!ledger.contains("/remind 11am x", now, "phone"));
+        ledger.record("/remind 11am x", now, "phone").unwrap();
+        ledger.record("/remind 11am\tx", now, "my\tphone").unwrap();
+        assert!(ledger.contains("/remind 11am x", now, "phone"));
+        drop(ledger);
+
+        /* tabs are cleaned so they can't break up a line */
+        let s = fs::read_to_string(&file).unwrap();
+        assert!(s.lines().all(|l| l.split('\t').count() == 3), "{}", s);
+
+        let ledger = Ledger::load(&file, now, keep).unwrap();
+        assert!(ledger.contains("/remind 11am x", now, "phone"));
+        assert!(ledger.contains("/remind 11am\tx", now, "my\tphone"));
+        assert!(ledger.contains("/remind 11am x", now, "my phone"));
+        assert!(!ledger.contains("/remind 11am x", now, "desk"));
+        drop(ledger);
+        remove(&file);
+    }
+
+    #[test]
+    fn load_prunes_old_entries() {
+        let file = ledger_file("prune");
+        let now = dt(2020, 4, 29, 11, 0);
+        fs::write(
+            &file,
+            "2020/04/20 11:00\tphone\told\n\
+             not a ledger line\n\
+             2020/04/28 11:00\tphone\trecent\n",
+        )
+        .unwrap();
+
+        let ledger = Ledger::load(&file, now, Duration::days(7)).unwrap();
+        assert!(!ledger.contains("old", dt(2020, 4, 20, 11, 0), "phone"));
+        assert!(ledger.contains("recent", dt(2020, 4, 28, 11, 0), "phone"));
+        drop(ledger);
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "2020/04/28 11:00\tphone\trecent\n"
+        );
+
+        remove(&file);
+    }
+
+    #[test]
+    fn retention_covers_catchup_max() {
+        let cfg = |s: &str| YamlLoader::load_from_str(s).unwrap().remove(0);
+        assert_eq!(retention(&cfg("file: x")), Duration::days(LEDGER_KEEP_DAYS));
+        assert_eq!(
+            retention(&cfg("catchup_max: 60")),
+            Duration::days(LEDGER_KEEP_DAYS)
+        );
+        assert_eq!(
+            retention(&cfg("catchup_max: 20160")),
+            Duration::minutes(20160)
+        );
+        /* an invalid catchup_max keeps entries for as long as any could be */
+        assert_eq!(
+            retention(&cfg("catchup_max: -1")),
+            Duration::minutes(MAX_CATCHUP_MAX)
+        );
+    }
+}
@@ -0,0 +1,33 @@
+/*
+ * The reminders library. This contains everything needed to load reminder
+ * sources, parse the reminder strings they contain, evaluate each reminder's
+ * schedule against a point in time, and send notifications. The `reminders`
+ * and `mdtodo` binaries are thin front-ends over this crate.
+ */
+
+#[macro_use]
+extern crate lazy_static;
+
+pub mod briefing;
+pub mod config;
+pub mod daemon;
+pub mod grammar;
+pub mod ledger;
+pub mod notify;
+pub mod reminder;
+pub mod report;
+pub mod run;
+pub mod schedule;
+pub mod source;
+pub mod state;
+pub mod todo;
+
+pub use crate::config::load_config;
+pub use crate::notify::{load_channels, Channel, Notification, Notifier};
+pub use crate::reminder::{check_reminder, due_between, Occurrence, Reminder};
+pub use crate::schedule::Schedule;
+pub use crate::source::{load_sources, Source};
+pub use crate::todo::TodoDoc;
+
+/* All fallible library calls return a boxed error. */
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -0,0 +1,126 @@
+use crate::config::{get_int, get_list, get_str};
+use crate::notify::{Notification, Notifier};
+use crate::Result;
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use yaml_rust::Yaml;
+
+/* How long (in seconds) the command can run when no `timeout:` is given. */
+pub const DEFAULT_EXEC_TIMEOUT: i64 = 30;
+
+/*
+ * Run a command for the notification. The notification is passed as JSON
+ * on stdin and in the REMINDER_TEXT, REMINDER_TIME, REMINDER_SOURCE,
+ * REMINDER_LINE and REMINDER_LATE environment variables. A command that
+ * fails or runs longer than the timeout (it's killed) is an error.
+ *
+ *   - type: exec
+ *     command: <command>      (a list, or a string run with sh -c)
+ *     timeout: <seconds>      (default 30)
+ */
+pub struct Exec {
+    argv: Vec<String>,
+    display: String,
+    timeout: Duration,
+}
+
+impl Exec {
+    pub fn from_config(cfg: &Yaml) -> Result<Exec> {
+        let argv = match get_str(cfg, "command") {
+            Some(c) => vec!["sh".to_string(), "-c".to_string(), c.to_string()],
+            None => get_list(cfg, "command"),
+        };
+        if argv.is_empty() {
+            return Err("exec channel requires 'command'".into());
+        }
+
+        let timeout = get_int(cfg, "timeout").unwrap_or(DEFAULT_EXEC_TIMEOUT);
+        if timeout <= 0 {
+            return Err(format!("invalid exec timeout ({})", timeout).into());
+        }
+
+        /* how the command is named in errors */
+        let display = match get_str(cfg, "command") {
+            Some(c) => c.to_string(),
+            None => argv[0].clone(),
+        };
+
+        Ok(Exec {
+            argv,
+            display,
+            timeout: Duration::from_secs(timeout as u64),
+        })
+    }
+}
+
+impl Notifier for Exec {
+    fn notify(&self, n: &Notification) -> Result<()> {
+        println!("exec: {:?} \"{}\"", n.at, n.text);
+
+        let time = n.time();
+        let input = n.to_json();
+
+        let mut child = match Command::new(&self.argv[0])
+            .args(&self.argv[1..])
+            .env("REMINDER_TEXT", &n.text)
+            .env("REMINDER_TITLE", n.title.as_deref().unwrap_or(""))
+            .env("REMINDER_TIME", &time)
+            .env("REMINDER_SOURCE", &n.source)
+            .env("REMINDER_LINE", n.line.to_string())
+            .env("REMINDER_LATE", n.late.to_string())
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            Ok(c) => c,
+            Err(e) => return Err(format!("failed to run '{}' ({})", self.display, e).into()),
+        };
+
+        /*
+         * The command doesn't have to read stdin so it's written from
+         * another thread, a command that never reads a large notification
+         * would otherwise block the write and the timeout.
+         */
+        let input = format!("{}\n", input);
+        let writer = child.stdin.take().map(|mut stdin| {
+            thread::spawn(move || match stdin.write_all(input.as_bytes()) {
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e),
+                _ => Ok(()),
+            })
+        });
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if start.elapsed() >= self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "'{}' timed out after {}s",
+                    self.display,
+                    self.timeout.as_secs()
+                )
+                .into());
+            }
+            thread::sleep(Duration::from_millis(50));
+        };
+
+        if !status.success() {
+            return Err(match status.code() {
+                Some(c) => format!("'{}' exited with status {}", self.display, c),
+                None => format!("'{}' was killed by a signal", self.display),
+            }
+            .into());
+        }
+
+        /* still blocked if something the command started holds stdin open */
+        if let Some(Ok(Err(e))) = writer.filter(|w| w.is_finished()).map(|w| w.join()) {
+            return Err(format!("failed to write to '{}' ({})", self.display, e).into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use crate::config::get_int;
+use crate::notify::{http_error, require, Notification, Notifier};
+use crate::Result;
+use serde_json::{json, Value};
+use yaml_rust::Yaml;
+
+/* The Gotify priority used when no `priority:` is given. */
+pub const DEFAULT_GOTIFY_PRIORITY: i64 = 5;
+
+/*
+ * Send the notification to a Gotify server. The priority is taken from the
+ * first `priorities:` tag found in the reminder text, else `priority:`.
+ *
+ *   - type: gotify
+ *     url: <server_url>
+ *     token: <app_token>
+ *     priority: <N>          (optional)
+ *     priorities:            (optional)
+ *       <tag>: <N>
+ */
+pub struct Gotify {
+    url: String,
+    token: String,
+    priority: i64,
+    priorities: Vec<(String, i64)>,
+}
+
+impl Gotify {
+    pub fn from_config(cfg: &Yaml) -> Result<Gotify> {
+        let url = require(cfg, "gotify", "url")?;
+
+        let mut priorities = Vec::new();
+        if let Some(h) = cfg["priorities"].as_hash() {
+            for (tag, p) in h {
+                match (tag.as_str(), p.as_i64()) {
+                    (Some(t), Some(p)) => {
+                        priorities.push((t.trim_start_matches('#').to_string(), p))
+                    }
+                    _ => return Err("gotify priorities must map a tag to a number".into()),
+                }
+            }
+        }
+
+        Ok(Gotify {
+            url: format!("{}/message", url.trim_end_matches('/')),
+            token: require(cfg, "gotify", "token")?.to_string(),
+            priority: get_int(cfg, "priority").unwrap_or(DEFAULT_GOTIFY_PRIORITY),
+            priorities,
+        })
+    }
+
+    /* The priority for the notification based on the tags in its text. */
+    fn priority(&self, n: &Notification) -> i64 {
+        self.priorities
+            .iter()
+            .find(|(tag, _)| n.has_tag(tag))
+            .map_or(self.priority, |(_, p)| *p)
+    }
+}
+
+impl Notifier for Gotify {
+    fn singly(&self, n: &Notification) -> bool {
+        !n.options.is_empty() || self.priorities.iter().any(|(tag, _)| n.has_tag(tag))
+    }
+
+    fn notify(&self, n: &Notification) -> Result<()> {
+        let priority = self.priority(n);
+        println!("gotify: {:?} \"{}\" (priority {})", n.at, n.text, priority);
+
+        let client = reqwest::Client::new();
+        let mut resp = client
+            .post(&self.url)
+            .header("X-Gotify-Key", self.token.as_str())
+            .json(&json!({ "title": n.title, "message": n.text, "priority": priority }))
+            .send()
+            .map_err(http_error)?;
+
+        if !resp.status().is_success() {
+            /* gotify returns {"error": ..., "errorCode": ..., "errorDescription": ...} */
+            let body: Value = resp.json().unwrap_or(Value::Null);
+            let desc = body["errorDescription"]
+                .as_str()
+                .or_else(|| body["error"].as_str())
+                .unwrap_or("no error description");
+            return Err(format!("gotify error {} ({})", resp.status(), desc).into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use crate::config::get_bool;
+use crate::notify::{http_error, require, Notification, Notifier};
+use crate::Result;
+use pulldown_cmark::{html, Parser};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use yaml_rust::Yaml;
+
+/*
+ * Send the notification to a Matrix room as an m.room.message. With
+ * `html: true` any markdown in the text is also sent formatted as HTML.
+ *
+ *   - type: matrix
+ *     homeserver: <url>
+ *     token: <access_token>
+ *     room_id: <!room:server>
+ *     html: <true|false>      (default false)
+ */
+pub struct Matrix {
+    url: String,
+    token: String,
+    html: bool,
+    txn: AtomicUsize,
+}
+
+/* Percent encode everything but the URL unreserved characters. */
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/* Render the markdown as HTML, without the wrapping paragraph for one line. */
+fn markdown_html(text: &str) -> String {
+    let mut out = String::new();
+    html::push_html(&mut out, Parser::new(text));
+
+    let out = out.trim_end();
+    match out.strip_prefix("<p>").and_then(|s| s.strip_suffix("</p>")) {
+        Some(inner) if !inner.contains("<p>") => inner.to_string(),
+        _ => out.to_string(),
+    }
+}
+
+impl Matrix {
+    pub fn from_config(cfg: &Yaml) -> Result<Matrix> {
+        let hs = require(cfg, "matrix", "homeserver")?;
+        let room = require(cfg, "matrix", "room_id")?;
+
+        Ok(Matrix {
+            url: format!(
+                "{}/_matrix/client/v3/rooms/{}/send/m.room.message",
+                hs.trim_end_matches('/'),
+                encode(room)
+            ),
+            token: require(cfg, "matrix", "token")?.to_string(),
+            html: get_bool(cfg, "html").unwrap_or(false),
+            txn: AtomicUsize::new(0),
+        })
+    }
+
+    /*
+     * A new transaction ID for each message. The homeserver ignores a repeat
+     * of an ID it has seen, so they must never be reused between messages.
+     */
+    fn txn_id(&self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        format!(
+            "reminders.{}.{}",
+            now,
+            self.txn.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+impl Notifier for Matrix {
+    fn notify(&self, n: &Notification) -> Result<()> {
+        println!("matrix: {:?} \"{}\"", n.at, n.text);
+
+        let mut msg = json!({ "msgtype": "m.text", "body": n.full_text() });
+        if self.html {
+            msg["format"] = json!("org.matrix.custom.html");
+            msg["formatted_body"] = json!(markdown_html(&n.full_text()));
+        }
+
+        let client = reqwest::Client::new();
+        let mut resp = client
+            .put(&format!("{}/{}", self.url, encode(&self.txn_id())))
+            .bearer_auth(&self.token)
+            .json(&msg)
+            .send()
+            .map_err(http_error)?;
+
+        if !resp.status().is_success() {
+            /* matrix returns {"errcode": ..., "error": ...} */
+            let body: Value = resp.json().unwrap_or(Value::Null);
+            let desc = body["error"].as_str().unwrap_or("no error description");
+            return Err(format!("matrix error {} ({})", resp.status(), desc).into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,382 @@
+/*
+ * Notification channels. Each channel is a Notifier implementation and the
+ * config's `channels:` list selects which ones are used. To add a new
+ * backend implement Notifier and add its `type:` to build_notifier().
+ */
+
+use crate::config::{get_bool, get_str};
+use crate::reminder::Reminder;
+use crate::todo::is_tag;
+use crate::Result;
+use chrono::NaiveDateTime;
+use serde_json::{json, Value};
+use std::env;
+use yaml_rust::Yaml;
+
+pub mod apprise;
+pub mod exec;
+pub mod gotify;
+pub mod matrix;
+pub mod mqtt;
+pub mod ntfy;
+pub mod pushover;
+pub mod ratelimit;
+pub mod receipts;
+pub mod smtp;
+pub mod stdout;
+pub mod telegram;
+pub mod webhook;
+
+pub use self::apprise::parse_url;
+pub use self::exec::Exec;
+pub use self::gotify::Gotify;
+pub use self::matrix::Matrix;
+pub use self::mqtt::Mqtt;
+pub use self::ntfy::Ntfy;
+pub use self::pushover::Pushover;
+pub use self::ratelimit::RateLimit;
+pub use self::smtp::Smtp;
+pub use self::stdout::Stdout;
+pub use self::telegram::Telegram;
+pub use self::webhook::Webhook;
+
+/* A single notification to be sent for a reminder occurrence. */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub text: String,
+    pub at: NaiveDateTime,
+    pub source: String,
+    pub line: usize,
+    pub late: bool,
+    pub options: Vec<(String, String)>,
+    pub title: Option<String>,
+}
+
+impl Notification {
+    pub fn new(text: &str, at: NaiveDateTime) -> Notification {
+        Notification {
+            text: text.to_string(),
+            at,
+            source: String::new(),
+            line: 0,
+            late: false,
+            options: Vec::new(),
+            title: None,
+        }
+    }
+
+    pub fn from_reminder(r: &Reminder, at: NaiveDateTime) -> Notification {
+        Notification {
+            text: r.text.clone(),
+            at,
+            source: r.source.clone(),
+            line: r.line,
+            late: false,
+            options: r.options.clone(),
+            title: None,
+        }
+    }
+
+    /* The text preceded by the title line, if there is a title. */
+    pub fn full_text(&self) -> String {
+        match &self.title {
+            Some(t) => format!("{}\n{}", t, self.text),
+            None => self.text.clone(),
+        }
+    }
+
+    /* The scheduled time as "YYYY/MM/DD HH:MM". */
+    pub fn time(&self) -> String {
+        self.at.format("%Y/%m/%d %H:%M").to_string()
+    }
+
+    /* The notification as a JSON object. */
+    pub fn to_json(&self) -> Value {
+        json!({
+            "text": self.text,
+            "title": self.title,
+            "time": self.time(),
+            "source": self.source,
+            "line": self.line,
+            "late": self.late,
+        })
+    }
+
+    /*
+     * Fill in a template's {{text}}, {{title}}, {{time}}, {{source}} and
+     * {{line}} placeholders, passing each value through escape.
+     */
+    pub fn render<F: Fn(&str) -> String>(&self, template: &str, escape: F) -> String {
+        template
+            .replace("{{text}}", &escape(&self.text))
+            .replace("{{title}}", &escape(self.title.as_deref().unwrap_or("")))
+            .replace("{{time}}", &escape(&self.time()))
+            .replace("{{source}}", &escape(&self.source))
+            .replace("{{line}}", &self.line.to_string())
+    }
+
+    /* Return true if the text contains the given tag (case insensitive). */
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.text
+            .split_whitespace()
+            .any(|w| is_tag(w) && w[1..].eq_ignore_ascii_case(tag))
+    }
+}
+
+/*
+ * Describe a failed HTTP request by the host instead of the full URL, which
+ * can contain a secret (e.g. a Telegram bot token or a Pushover app token).
+ */
+pub fn http_error(e: reqwest::Error) -> String {
+    let msg = e.to_string();
+    match e.url() {
+        Some(url) => format!(
+            "{}: {}",
+            url.host_str().unwrap_or("unknown host"),
+            msg.trim_start_matches(&format!("{}: ", url))
+        ),
+        None => msg,
+    }
+}
+
+/*
+ * Combine notifications into a single digest listing each one's text with
+ * the count in the title. The digest is for the latest notification's time.
+ */
+pub fn digest(ns: &[&Notification]) -> Notification {
+    let text: Vec<String> = ns.iter().map(|n| format!("- {}", n.text)).collect();
+    let at = ns.iter().map(|n| n.at).max().expect("empty digest");
+
+    let mut d = Notification::new(&text.join("\n"), at);
+    d.late = ns.iter().all(|n| n.late);
+    d.title = Some(format!("{} reminders", ns.len()));
+    d
+}
+
+/* A notification to send on another channel (e.g. one not acknowledged). */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fallback {
+    pub channel: String,
+    pub notification: Notification,
+}
+
+/* A notification backend. */
+pub trait Notifier: Send + Sync {
+    fn notify(&self, n: &Notification) -> Result<()>;
+
+    /*
+     * Check on earlier notifications (e.g. if they were acknowledged) adding
+     * any that must now be sent to a fallback channel.
+     */
+    fn follow_up(&self, _fallbacks: &mut Vec<Fallback>) -> Result<()> {
+        Ok(())
+    }
+
+    /* The name of the channel follow_up() sends fallbacks to, if any. */
+    fn fallback(&self) -> Option<&str> {
+        None
+    }
+
+    /* The rate limit used when the channel has no `rate_limit:`. */
+    fn default_rate_limit(&self) -> Option<RateLimit> {
+        None
+    }
+
+    /*
+     * Return true if the notification has message options of its own (e.g.
+     * a priority) and so must be sent on its own instead of in a digest.
+     */
+    fn singly(&self, n: &Notification) -> bool {
+        !n.options.is_empty()
+    }
+}
+
+/*
+ * A configured notifier. The name identifies the channel in the delivery
+ * ledger and error reports and defaults to the channel's type. A fallback
+ * only channel is only sent notifications another channel falls back on.
+ * A channel with a rate limit waits as needed before each notification.
+ * A channel with a digest threshold is sent a single digest when more than
+ * that many notifications are due at once.
+ */
+pub struct Channel {
+    pub name: String,
+    pub notifier: Box<dyn Notifier>,
+    pub fallback_only: bool,
+    pub rate_limit: Option<RateLimit>,
+    pub digest: Option<usize>,
+}
+
+impl Channel {
+    pub fn new(name: &str, notifier: Box<dyn Notifier>) -> Channel {
+        Channel {
+            name: name.to_string(),
+            rate_limit: notifier.default_rate_limit(),
+            notifier,
+            fallback_only: false,
+            digest: None,
+        }
+    }
+
+    pub fn notify(&self, n: &Notification) -> Result<()> {
+        if let Some(rl) = &self.rate_limit {
+            rl.wait();
+        }
+        self.notifier.notify(n)
+    }
+
+    pub fn singly(&self, n: &Notification) -> bool {
+        self.notifier.singly(n)
+    }
+
+    pub fn follow_up(&self, fallbacks: &mut Vec<Fallback>) -> Result<()> {
+        self.notifier.follow_up(fallbacks)
+    }
+}
+
+/* Return a channel variable that must be present. */
+pub fn require<'a>(cfg: &'a Yaml, kind: &str, key: &str) -> Result<&'a str> {
+    match get_str(cfg, key) {
+        Some(v) => Ok(v),
+        None => Err(format!("{} channel requires '{}'", kind, key).into()),
+    }
+}
+
+/* Create the notifier for a single entry in the `channels:` list. */
+pub fn build_notifier(kind: &str, cfg: &Yaml) -> Result<Box<dyn Notifier>> {
+    match kind {
+        "exec" => Ok(Box::new(Exec::from_config(cfg)?)),
+        "gotify" => Ok(Box::new(Gotify::from_config(cfg)?)),
+        "matrix" => Ok(Box::new(Matrix::from_config(cfg)?)),
+        "mqtt" => Ok(Box::new(Mqtt::from_config(cfg)?)),
+        "ntfy" => Ok(Box::new(Ntfy::from_config(cfg)?)),
+        "pushover" => Ok(Box::new(Pushover::from_config(cfg)?)),
+        "smtp" => Ok(Box::new(Smtp::from_config(cfg)?)),
+        "stdout" => Ok(Box::new(Stdout)),
+        "telegram" => Ok(Box::new(Telegram::from_config(cfg)?)),
+        "webhook" => Ok(Box::new(Webhook::from_config(cfg)?)),
+        _ => Err(format!("unknown channel type ({})", kind).into()),
+    }
+}
+
+/* The environment variable holding more channel URLs (space separated). */
+pub const NOTIFY_ENV: &str = "REMINDERS_NOTIFY";
+
+/*
+ * The channel URLs from the config's `notify:` (a list or a space separated
+ * string) followed by those in $REMINDERS_NOTIFY. Each is paired with where
+ * it came from (e.g. "notify[2]") to report errors without the URL.
+ */
+fn notify_urls(cfg: &Yaml) -> Vec<(String, String)> {
+    let urls: Vec<&str> = match &cfg["notify"] {
+        Yaml::Array(a) => a.iter().filter_map(|u| u.as_str()).collect(),
+        Yaml::String(s) => s.split_whitespace().collect(),
+        _ => Vec::new(),
+    };
+    let mut v: Vec<(String, String)> = urls
+        .into_iter()
+        .enumerate()
+        .map(|(i, u)| (format!("notify[{}]", i), u.to_string()))
+        .collect();
+
+    if let Ok(env) = env::var(NOTIFY_ENV) {
+        for (i, u) in env.split_whitespace().enumerate() {
+            v.push((format!("{}[{}]", NOTIFY_ENV, i), u.to_string()));
+        }
+    }
+
+    v
+}
+
+/*
+ * Parse a `digest:` threshold. True is a digest whenever more than one
+ * notification is due, false is never.
+ */
+fn digest_threshold(v: &Yaml) -> Result<Option<usize>> {
+    match v {
+        Yaml::BadValue | Yaml::Null | Yaml::Boolean(false) => Ok(None),
+        Yaml::Boolean(true) => Ok(Some(1)),
+        Yaml::Integer(n) if *n >= 0 => Ok(Some((*n as usize).max(1))),
+        _ => Err("digest must be true, false or a number".into()),
+    }
+}
+
+/*
+ * Create every enabled channel in the config's `channels:` list and every
+ * channel URL in `notify:` and $REMINDERS_NOTIFY. With none of these the
+ * old top level `pushover_app_token:` and `pushover_user_key:` are used,
+ * else notifications go to stdout. Channel names must be unique as the
+ * delivery ledger is keyed by them and a fallback must name a channel.
+ */
+pub fn load_channels(cfg: &Yaml) -> Result<Vec<Channel>> {
+    let digest = digest_threshold(&cfg["digest"])?;
+
+    let mut list: Vec<Yaml> = cfg["channels"].as_vec().cloned().unwrap_or_default();
+    for (from, u) in notify_urls(cfg) {
+        let scheme = match u.find("://") {
+            Some(i) => &u[..i],
+            None => "no scheme",
+        };
+        match parse_url(&u) {
+            Ok(c) => list.push(c),
+            Err(e) => return Err(format!("{} ({}): {}", from, scheme, e).into()),
+        }
+    }
+
+    if list.is_empty() && cfg["channels"].as_vec().is_none() {
+        if get_str(cfg, "pushover_app_token").is_some()
+            && get_str(cfg, "pushover_user_key").is_some()
+        {
+            let p = Pushover::new(
+                get_str(cfg, "pushover_app_token").unwrap(),
+                get_str(cfg, "pushover_user_key").unwrap(),
+            );
+            let mut ch = Channel::new("pushover", Box::new(p));
+            ch.digest = digest;
+            return Ok(vec![ch]);
+        }
+        let mut ch = Channel::new("stdout", Box::new(Stdout));
+        ch.digest = digest;
+        return Ok(vec![ch]);
+    }
+
+    let mut channels = Vec::new();
+    for c in &list {
+        let kind = match get_str(c, "type") {
+            Some(k) => k,
+            None => return Err("channel missing 'type'".into()),
+        };
+
+        if !get_bool(c, "enabled").unwrap_or(true) {
+            continue;
+        }
+
+        let name = get_str(c, "name").unwrap_or(kind);
+        if channels.iter().any(|ch: &Channel| ch.name == name) {
+            return Err(format!(
+                "duplicate channel name '{}' (give each channel a different 'name')",
+                name
+            )
+            .into());
+        }
+
+        let mut ch = Channel::new(name, build_notifier(kind, c)?);
+        ch.fallback_only = get_bool(c, "fallback_only").unwrap_or(false);
+        ch.rate_limit = RateLimit::from_config(c, ch.rate_limit.take())?;
+        ch.digest = match c["digest"].is_badvalue() {
+            true => digest,
+            false => digest_threshold(&c["digest"])?,
+        };
+        channels.push(ch);
+    }
+
+    for ch in &channels {
+        if let Some(f) = ch.notifier.fallback() {
+            if !channels.iter().any(|c| c.name == f) {
+                return Err(format!("unknown fallback channel '{}' for {}", f, ch.name).into());
+            }
+        }
+    }
+
+    Ok(channels)
+}
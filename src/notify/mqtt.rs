@@ -0,0 +1,143 @@
+use crate::config::{get_bool, get_int, get_str};
+use crate::notify::{require, Notification, Notifier};
+use crate::Result;
+use rumqttc::{Client, Event, MqttOptions, Outgoing, Packet, QoS};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use yaml_rust::Yaml;
+
+/* The broker port used when no `port:` is given. */
+pub const DEFAULT_MQTT_PORT: i64 = 1883;
+
+/* The topic used when no `topic:` is given. */
+pub const DEFAULT_MQTT_TOPIC: &str = "reminders";
+
+/* How long to wait for the broker to accept the message. */
+const MQTT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/*
+ * Counts the connections made so each gets its own client id. Channels are
+ * sent to at the same time and a broker drops a connection when another
+ * connects with the same id.
+ */
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/*
+ * Publish the notification as JSON to an MQTT broker. The topic is a
+ * template where {{text}}, {{time}}, {{source}} and {{line}} are replaced
+ * by the notification's values.
+ *
+ *   - type: mqtt
+ *     host: <host>
+ *     port: <port>            (default 1883)
+ *     username: <username>    (optional)
+ *     password: <password>    (optional)
+ *     topic: <template>       (default reminders)
+ *     qos: <0|1|2>            (default 0)
+ *     retain: <true|false>    (default false)
+ */
+pub struct Mqtt {
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+    topic: String,
+    qos: QoS,
+    retain: bool,
+}
+
+/* Replace the characters that aren't allowed in a published topic. */
+fn topic_escape(s: &str) -> String {
+    s.replace(['+', '#'], "_")
+}
+
+impl Mqtt {
+    pub fn from_config(cfg: &Yaml) -> Result<Mqtt> {
+        let port = match get_int(cfg, "port").unwrap_or(DEFAULT_MQTT_PORT) {
+            p if p > 0 && p <= i64::from(u16::MAX) => p as u16,
+            p => return Err(format!("invalid mqtt port ({})", p).into()),
+        };
+
+        let qos = match get_int(cfg, "qos").unwrap_or(0) {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            q => return Err(format!("invalid mqtt qos ({})", q).into()),
+        };
+
+        let credentials = match (get_str(cfg, "username"), get_str(cfg, "password")) {
+            (Some(u), p) => Some((u.to_string(), p.unwrap_or("").to_string())),
+            (None, None) => None,
+            (None, Some(_)) => return Err("mqtt channel 'password' requires 'username'".into()),
+        };
+
+        Ok(Mqtt {
+            host: require(cfg, "mqtt", "host")?.to_string(),
+            port,
+            credentials,
+            topic: get_str(cfg, "topic")
+                .unwrap_or(DEFAULT_MQTT_TOPIC)
+                .to_string(),
+            qos,
+            retain: get_bool(cfg, "retain").unwrap_or(false),
+        })
+    }
+}
+
+impl Notifier for Mqtt {
+    fn notify(&self, n: &Notification) -> Result<()> {
+        let topic = n.render(&self.topic, topic_escape);
+        println!("mqtt: {:?} \"{}\" ({})", n.at, n.text, topic);
+
+        let mut opts = MqttOptions::new(
+            format!(
+                "reminders-{}-{}",
+                process::id(),
+                CONNECTIONS.fetch_add(1, Ordering::Relaxed)
+            ),
+            self.host.as_str(),
+            self.port,
+        );
+        opts.set_keep_alive(Duration::from_secs(30));
+        if let Some((u, p)) = &self.credentials {
+            opts.set_credentials(u.as_str(), p.as_str());
+        }
+
+        let (client, mut conn) = Client::new(opts, 10);
+        client.publish(topic, self.qos, self.retain, n.to_json().to_string())?;
+
+        /* drive the connection until the broker has the message */
+        let start = Instant::now();
+        loop {
+            let left = match MQTT_TIMEOUT.checked_sub(start.elapsed()) {
+                Some(l) => l,
+                None => return Err("mqtt publish timed out".into()),
+            };
+            let event = match conn.recv_timeout(left) {
+                Ok(Ok(e)) => e,
+                Ok(Err(e)) => return Err(format!("mqtt error ({})", e).into()),
+                Err(_) => return Err("mqtt publish timed out".into()),
+            };
+
+            if matches!(
+                (self.qos, event),
+                (QoS::AtMostOnce, Event::Outgoing(Outgoing::Publish(_)))
+                    | (QoS::AtLeastOnce, Event::Incoming(Packet::PubAck(_)))
+                    | (QoS::ExactlyOnce, Event::Incoming(Packet::PubComp(_)))
+            ) {
+                break;
+            }
+        }
+
+        /* a clean disconnect so the message isn't dropped */
+        client.disconnect()?;
+        for event in conn.iter() {
+            match event {
+                Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => break,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
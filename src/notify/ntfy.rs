@@ -0,0 +1,77 @@
+use crate::config::{get_int, get_list, get_str};
+use crate::notify::{http_error, require, Notification, Notifier};
+use crate::Result;
+use yaml_rust::Yaml;
+
+/* The public ntfy server used when no `server:` is given. */
+pub const DEFAULT_NTFY_SERVER: &str = "https://ntfy.sh";
+
+/*
+ * Publish the notification to an ntfy topic.
+ *
+ *   - type: ntfy
+ *     server: <url>          (default https://ntfy.sh)
+ *     topic: <topic>
+ *     token: <access_token>  (optional)
+ *     priority: <1-5|name>   (optional)
+ *     tags: <tag,...>        (optional)
+ *     click: <url>           (optional)
+ */
+pub struct Ntfy {
+    url: String,
+    token: Option<String>,
+    priority: Option<String>,
+    tags: Vec<String>,
+    click: Option<String>,
+}
+
+impl Ntfy {
+    pub fn from_config(cfg: &Yaml) -> Result<Ntfy> {
+        let server = get_str(cfg, "server").unwrap_or(DEFAULT_NTFY_SERVER);
+        let topic = require(cfg, "ntfy", "topic")?;
+
+        let priority = match get_int(cfg, "priority") {
+            Some(p) => Some(p.to_string()),
+            None => get_str(cfg, "priority").map(|p| p.to_string()),
+        };
+
+        Ok(Ntfy {
+            url: format!("{}/{}", server.trim_end_matches('/'), topic),
+            token: get_str(cfg, "token").map(|t| t.to_string()),
+            priority,
+            tags: get_list(cfg, "tags"),
+            click: get_str(cfg, "click").map(|c| c.to_string()),
+        })
+    }
+}
+
+impl Notifier for Ntfy {
+    fn notify(&self, n: &Notification) -> Result<()> {
+        println!("ntfy: {:?} \"{}\"", n.at, n.text);
+
+        let client = reqwest::Client::new();
+        let mut req = client.post(&self.url).body(n.text.clone());
+
+        if let Some(t) = &n.title {
+            req = req.header("Title", t.as_str());
+        }
+        if let Some(t) = &self.token {
+            req = req.bearer_auth(t);
+        }
+        if let Some(p) = &self.priority {
+            req = req.header("Priority", p.as_str());
+        }
+        if !self.tags.is_empty() {
+            req = req.header("Tags", self.tags.join(","));
+        }
+        if let Some(c) = &self.click {
+            req = req.header("Click", c.as_str());
+        }
+
+        req.send()
+            .and_then(|r| r.error_for_status())
+            .map_err(http_error)?;
+
+        Ok(())
+    }
+}
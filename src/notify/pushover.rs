@@ -0,0 +1,438 @@
+use crate::config::{get_int, get_str};
+use crate::daemon::now_minute;
+use crate::notify::receipts::{
+    add_receipt, load_receipts, lock_receipts, save_receipts, Receipt, Status,
+};
+use crate::notify::{http_error, require, Fallback, Notification, Notifier, RateLimit};
+use crate::Result;
+use chrono::{Local, TimeZone};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use std::{thread, time};
+use yaml_rust::Yaml;
+
+/* The Pushover API used when no `api_url:` is given. */
+pub const DEFAULT_PUSHOVER_API: &str = "https://api.pushover.net";
+
+/* The message options that can be set per channel, tag or reminder. */
+pub const PUSHOVER_OPTIONS: &[&str] = &[
+    "title",
+    "priority",
+    "sound",
+    "url",
+    "url_title",
+    "device",
+    "ttl",
+    "retry",
+    "expire",
+];
+
+/* How many times a failed API call is retried when no `max_retries:` is given. */
+pub const DEFAULT_MAX_RETRIES: i64 = 3;
+
+/* The first retry delay (in seconds) when no `backoff:` is given. */
+pub const DEFAULT_BACKOFF: i64 = 1;
+
+/* Emergency (priority 2) messages require how often and long to retry. */
+const EMERGENCY_RETRY: &str = "60";
+const EMERGENCY_EXPIRE: &str = "3600";
+
+type Options = Vec<(String, String)>;
+
+/* A YAML scalar as a string. */
+fn scalar(v: &Yaml) -> Option<String> {
+    match v {
+        Yaml::String(s) => Some(s.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/* Check the value of a Pushover option. */
+fn check_option(key: &str, value: &str) -> Result<()> {
+    let ok = match key {
+        "priority" => value.parse::<i8>().is_ok_and(|p| (-2..=2).contains(&p)),
+        "ttl" => value.parse::<u32>().is_ok_and(|t| t > 0),
+        "retry" => value.parse::<u32>().is_ok_and(|r| r >= 30),
+        "expire" => value.parse::<u32>().is_ok_and(|e| e > 0 && e <= 10800),
+        _ => true,
+    };
+
+    match ok {
+        true => Ok(()),
+        false => Err(format!("invalid pushover {} ({})", key, value).into()),
+    }
+}
+
+/* Collect the Pushover options in a config hash, ignoring other keys. */
+fn options(cfg: &Yaml) -> Result<Options> {
+    let mut opts = Vec::new();
+    for key in PUSHOVER_OPTIONS {
+        if let Some(v) = scalar(&cfg[*key]) {
+            check_option(key, &v)?;
+            opts.push((key.to_string(), v));
+        }
+    }
+
+    Ok(opts)
+}
+
+/* The app's remaining message quota from the X-Limit-App-* headers. */
+#[derive(Clone, Copy, Debug)]
+struct Quota {
+    remaining: i64,
+    reset: i64,
+}
+
+/* Read the quota saved by save_quota(). A missing or invalid file has none. */
+fn load_quota(file: &str) -> Option<Quota> {
+    let s = fs::read_to_string(file).ok()?;
+    let mut f = s.split_whitespace();
+    Some(Quota {
+        remaining: f.next()?.parse().ok()?,
+        reset: f.next()?.parse().ok()?,
+    })
+}
+
+/* Save the quota as "<remaining> <reset>" (reset is a Unix timestamp). */
+fn save_quota(file: &str, q: Quota) -> Result<()> {
+    if let Err(e) = fs::write(file, format!("{} {}\n", q.remaining, q.reset)) {
+        return Err(format!("failed to write quota file {} ({})", file, e).into());
+    }
+
+    Ok(())
+}
+
+fn header_int(resp: &reqwest::Response, name: &str) -> Option<i64> {
+    resp.headers().get(name)?.to_str().ok()?.parse().ok()
+}
+
+fn unix_now() -> i64 {
+    Local::now().timestamp()
+}
+
+/*
+ * Send the notification to Pushover. The message options are taken from
+ * the channel, then any `tags:` found in the reminder text (in order) and
+ * finally the reminder's inline `!key=value` options.
+ *
+ * With a `receipts_file:` the receipt for every emergency (priority 2)
+ * message is saved and polled until it's acknowledged or expires. One that
+ * expires is sent to the `fallback:` channel.
+ *
+ * Network errors and server failures are retried `max_retries:` times,
+ * waiting `backoff:` seconds before the first retry and doubling the wait
+ * each time. A request Pushover rejects isn't retried and once the app's
+ * monthly quota is used up nothing is sent until it resets. The quota is
+ * saved in the `quota_file:` so it's known to the next run.
+ *
+ *   - type: pushover
+ *     app_token: <app_token>
+ *     user_key: <user_key>
+ *     api_url: <url>          (default https://api.pushover.net)
+ *     title: <title>          (optional, also priority, sound, url,
+ *                              url_title, device, ttl, retry and expire)
+ *     tags:                   (optional)
+ *       <tag>:
+ *         priority: 1
+ *         sound: siren
+ *     receipts_file: <file>   (optional)
+ *     fallback: <channel>     (optional)
+ *     quota_file: <file>      (optional)
+ */
+pub struct Pushover {
+    app_token: String,
+    user_key: String,
+    api: String,
+    options: Options,
+    tags: Vec<(String, Options)>,
+    receipts: Option<String>,
+    fallback: Option<String>,
+    max_retries: u32,
+    backoff: u64,
+    quota: Mutex<Option<Quota>>,
+    quota_file: Option<String>,
+}
+
+impl Pushover {
+    pub fn new(app_token: &str, user_key: &str) -> Pushover {
+        Pushover {
+            app_token: app_token.to_string(),
+            user_key: user_key.to_string(),
+            api: DEFAULT_PUSHOVER_API.to_string(),
+            options: Vec::new(),
+            tags: Vec::new(),
+            receipts: None,
+            fallback: None,
+            max_retries: DEFAULT_MAX_RETRIES as u32,
+            backoff: DEFAULT_BACKOFF as u64,
+            quota: Mutex::new(None),
+            quota_file: None,
+        }
+    }
+
+    pub fn from_config(cfg: &Yaml) -> Result<Pushover> {
+        let mut p = Pushover::new(
+            require(cfg, "pushover", "app_token")?,
+            require(cfg, "pushover", "user_key")?,
+        );
+        p.options = options(cfg)?;
+        p.receipts = get_str(cfg, "receipts_file").map(String::from);
+        p.fallback = get_str(cfg, "fallback").map(String::from);
+        if p.fallback.is_some() && p.receipts.is_none() {
+            return Err("pushover 'fallback' requires 'receipts_file'".into());
+        }
+        p.max_retries = match get_int(cfg, "max_retries").unwrap_or(DEFAULT_MAX_RETRIES) {
+            r if (0..=10).contains(&r) => r as u32,
+            r => return Err(format!("invalid pushover max_retries ({})", r).into()),
+        };
+        p.backoff = match get_int(cfg, "backoff").unwrap_or(DEFAULT_BACKOFF) {
+            b if b >= 0 => b as u64,
+            b => return Err(format!("invalid pushover backoff ({})", b).into()),
+        };
+        p.quota_file = get_str(cfg, "quota_file").map(String::from);
+        if let Some(file) = &p.quota_file {
+            p.quota = Mutex::new(load_quota(file));
+        }
+        if let Some(api) = get_str(cfg, "api_url") {
+            p.api = api.trim_end_matches('/').to_string();
+        }
+
+        if let Some(h) = cfg["tags"].as_hash() {
+            for (tag, opts) in h {
+                match tag.as_str() {
+                    Some(t) if opts.as_hash().is_some() => p
+                        .tags
+                        .push((t.trim_start_matches('#').to_string(), options(opts)?)),
+                    _ => return Err("pushover tags must map a tag to options".into()),
+                }
+            }
+        }
+
+        Ok(p)
+    }
+
+    /* The message options for the notification. */
+    fn message_options(&self, n: &Notification) -> Result<HashMap<String, String>> {
+        let mut opts: HashMap<String, String> = self.options.iter().cloned().collect();
+
+        for (tag, o) in &self.tags {
+            if n.has_tag(tag) {
+                opts.extend(o.iter().cloned());
+            }
+        }
+
+        for (k, v) in &n.options {
+            if PUSHOVER_OPTIONS.contains(&k.as_str()) {
+                check_option(k, v)?;
+                opts.insert(k.clone(), v.clone());
+            }
+        }
+
+        if opts.get("priority").map(|p| p.as_str()) == Some("2") {
+            opts.entry("retry".to_string())
+                .or_insert_with(|| EMERGENCY_RETRY.to_string());
+            opts.entry("expire".to_string())
+                .or_insert_with(|| EMERGENCY_EXPIRE.to_string());
+        }
+
+        Ok(opts)
+    }
+
+    /* Fail if the app's quota is used up and hasn't reset yet. */
+    fn check_quota(&self) -> Result<()> {
+        match *self.quota.lock().unwrap() {
+            Some(q) if q.remaining <= 0 && unix_now() < q.reset => {
+                let reset = Local.timestamp_opt(q.reset, 0).single();
+                let reset = reset.map_or(q.reset.to_string(), |t| {
+                    t.format("%Y/%m/%d %H:%M").to_string()
+                });
+                Err(format!("pushover message quota used up until {}", reset).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn update_quota(&self, resp: &reqwest::Response) {
+        let remaining = header_int(resp, "X-Limit-App-Remaining");
+        let reset = header_int(resp, "X-Limit-App-Reset");
+        if let (Some(remaining), Some(reset)) = (remaining, reset) {
+            let q = Quota { remaining, reset };
+            *self.quota.lock().unwrap() = Some(q);
+            if let Some(file) = &self.quota_file {
+                if let Err(e) = save_quota(file, q) {
+                    eprintln!("pushover: {}", e);
+                }
+            }
+        }
+    }
+
+    /*
+     * Make an API call returning the JSON response. Network errors and
+     * server failures are retried with exponential backoff, a request
+     * Pushover rejects (i.e. "status" isn't 1) is an error right away.
+     */
+    fn call<F: Fn() -> reqwest::RequestBuilder>(&self, request: F) -> Result<Value> {
+        let mut delay = self.backoff;
+        let mut failure = String::new();
+
+        for attempt in 0..=self.max_retries {
+            if attempt > 0 {
+                eprintln!("pushover: {}, retrying in {}s", failure, delay);
+                thread::sleep(time::Duration::from_secs(delay));
+                delay *= 2;
+            }
+
+            let mut resp = match request().send() {
+                Ok(r) => r,
+                Err(e) => {
+                    failure = format!("request failed ({})", http_error(e));
+                    continue;
+                }
+            };
+            self.update_quota(&resp);
+
+            let status = resp.status();
+            let body: Value = resp.json().unwrap_or(Value::Null);
+            if status.is_server_error() {
+                failure = format!("server error {}", status);
+                continue;
+            }
+
+            if status.is_success() && body["status"].as_i64() == Some(1) {
+                return Ok(body);
+            }
+
+            /* pushover returns {"status": 0, "errors": [...]} */
+            let errors: Vec<&str> = match body["errors"].as_array() {
+                Some(e) => e.iter().filter_map(|e| e.as_str()).collect(),
+                None => vec!["unexpected response"],
+            };
+            if status.as_u16() == 429 {
+                return Err(
+                    format!("pushover message quota used up ({})", errors.join(", ")).into(),
+                );
+            }
+            return Err(format!(
+                "pushover rejected the request {} ({})",
+                status,
+                errors.join(", ")
+            )
+            .into());
+        }
+
+        Err(format!(
+            "pushover {} after {} attempts",
+            failure,
+            self.max_retries + 1
+        )
+        .into())
+    }
+}
+
+impl Notifier for Pushover {
+    fn singly(&self, n: &Notification) -> bool {
+        !n.options.is_empty() || self.tags.iter().any(|(tag, _)| n.has_tag(tag))
+    }
+
+    fn notify(&self, n: &Notification) -> Result<()> {
+        let mut map = self.message_options(n)?;
+        map.insert("message".to_string(), n.text.clone());
+        if let Some(t) = &n.title {
+            map.insert("title".to_string(), t.clone());
+        }
+        map.insert("token".to_string(), self.app_token.clone());
+        map.insert("user".to_string(), self.user_key.clone());
+
+        println!("pushover: {:?} \"{}\"", n.at, n.text);
+        self.check_quota()?;
+
+        let client = reqwest::Client::new();
+        let url = format!("{}/1/messages.json", self.api);
+        let body = self.call(|| client.post(&url).json(&map))?;
+
+        /* an emergency message returns a receipt to poll */
+        if let (Some(file), Some(receipt)) = (&self.receipts, body["receipt"].as_str()) {
+            add_receipt(
+                file,
+                &Receipt {
+                    at: n.at,
+                    receipt: receipt.to_string(),
+                    status: Status::Pending,
+                    source: n.source.clone(),
+                    line: n.line,
+                    text: n.text.clone(),
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn fallback(&self) -> Option<&str> {
+        self.fallback.as_deref()
+    }
+
+    /* iOS drops some notifications when spammed so send one every 2s */
+    fn default_rate_limit(&self) -> Option<RateLimit> {
+        Some(RateLimit::new(1, time::Duration::from_secs(2)))
+    }
+
+    /* Poll the pending receipts, sending expired ones to the fallback. */
+    fn follow_up(&self, fallbacks: &mut Vec<Fallback>) -> Result<()> {
+        let file = match &self.receipts {
+            Some(f) => f,
+            None => return Ok(()),
+        };
+
+        let _lock = lock_receipts(file)?;
+        let mut receipts = load_receipts(file)?;
+        let mut rc = Ok(());
+
+        let client = reqwest::Client::new();
+        for r in receipts.iter_mut().filter(|r| r.status == Status::Pending) {
+            let url = format!(
+                "{}/1/receipts/{}.json?token={}",
+                self.api, r.receipt, self.app_token
+            );
+            let body = match self.call(|| client.get(&url)) {
+                Ok(b) => b,
+                Err(e) => {
+                    rc = Err(format!("receipt {} poll failed ({})", r.receipt, e));
+                    continue;
+                }
+            };
+
+            if body["acknowledged"].as_i64() == Some(1) {
+                let secs = body["acknowledged_at"].as_i64().unwrap_or(0);
+                let at = Local
+                    .timestamp_opt(secs, 0)
+                    .single()
+                    .map(|t| t.naive_local());
+                let at = at.unwrap_or_else(now_minute);
+                println!("pushover: \"{}\" acknowledged at {:?}", r.text, at);
+                r.status = Status::Acknowledged(at);
+            } else if body["expired"].as_i64() == Some(1) {
+                println!("pushover: \"{}\" expired unacknowledged", r.text);
+                r.status = Status::Expired;
+
+                if let Some(fb) = &self.fallback {
+                    let mut n = Notification::new(&format!("{} (not acknowledged)", r.text), r.at);
+                    n.source = r.source.clone();
+                    n.line = r.line;
+                    fallbacks.push(Fallback {
+                        channel: fb.clone(),
+                        notification: n,
+                    });
+                }
+            }
+        }
+
+        save_receipts(file, &receipts, now_minute())?;
+
+        Ok(rc?)
+    }
+}
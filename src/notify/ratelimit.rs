@@ -0,0 +1,78 @@
+use crate::Result;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use yaml_rust::Yaml;
+
+/*
+ * A token bucket limiting how fast a channel sends. Up to `burst`
+ * notifications are sent right away, after that one every `interval`.
+ */
+#[derive(Debug)]
+pub struct RateLimit {
+    burst: u32,
+    interval: Duration,
+    /* the tokens available and when they were last refilled */
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimit {
+    pub fn new(burst: u32, interval: Duration) -> RateLimit {
+        RateLimit {
+            burst: burst.max(1),
+            interval,
+            state: Mutex::new((f64::from(burst.max(1)), Instant::now())),
+        }
+    }
+
+    /*
+     * Parse a channel's `rate_limit:`, either a mapping with `burst:`
+     * (default 1) and `interval:` seconds, or false for no limit. Returns
+     * the default if there's no `rate_limit:`.
+     */
+    pub fn from_config(cfg: &Yaml, default: Option<RateLimit>) -> Result<Option<RateLimit>> {
+        let rl = &cfg["rate_limit"];
+        if rl.is_badvalue() || rl.is_null() {
+            return Ok(default);
+        }
+        if rl.as_bool() == Some(false) {
+            return Ok(None);
+        }
+
+        let burst = match rl["burst"].as_i64().unwrap_or(1) {
+            b if b >= 1 && b <= i64::from(u32::MAX) => b as u32,
+            b => return Err(format!("invalid rate_limit burst ({})", b).into()),
+        };
+        let interval = match rl["interval"]
+            .as_f64()
+            .or_else(|| rl["interval"].as_i64().map(|i| i as f64))
+        {
+            Some(i) if i > 0.0 => match Duration::try_from_secs_f64(i) {
+                Ok(d) => d,
+                Err(_) => return Err("rate_limit interval is too long".into()),
+            },
+            _ => return Err("rate_limit requires a positive 'interval'".into()),
+        };
+
+        Ok(Some(RateLimit::new(burst, interval)))
+    }
+
+    /* Block until the channel is allowed to send. */
+    pub fn wait(&self) {
+        let mut state = self.state.lock().unwrap();
+        let (tokens, last) = &mut *state;
+
+        let now = Instant::now();
+        let refill = now.duration_since(*last).as_secs_f64() / self.interval.as_secs_f64();
+        *tokens = (*tokens + refill).min(f64::from(self.burst));
+        *last = now;
+
+        if *tokens < 1.0 {
+            let wait = self.interval.mul_f64(1.0 - *tokens);
+            thread::sleep(wait);
+            *tokens = 1.0;
+            *last = Instant::now();
+        }
+        *tokens -= 1.0;
+    }
+}
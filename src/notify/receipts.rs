@@ -0,0 +1,137 @@
+use crate::Result;
+use chrono::{Duration, NaiveDateTime};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+
+const RECEIPT_FMT: &str = "%Y/%m/%d %H:%M";
+
+/* How long finished receipts are remembered. */
+pub const RECEIPTS_KEEP_DAYS: i64 = 7;
+
+/* What's known about an emergency notification. */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Pending,
+    Acknowledged(NaiveDateTime),
+    Expired,
+}
+
+/*
+ * An emergency notification waiting to be acknowledged. Each line in the
+ * receipts file is "<time>\t<receipt>\t<status>\t<source>\t<line>\t<text>"
+ * where the status is "pending", "expired" or "acknowledged <time>".
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Receipt {
+    pub at: NaiveDateTime,
+    pub receipt: String,
+    pub status: Status,
+    pub source: String,
+    pub line: usize,
+    pub text: String,
+}
+
+fn clean(s: &str) -> String {
+    s.replace(['\t', '\n'], " ")
+}
+
+impl Receipt {
+    fn parse(line: &str) -> Option<Receipt> {
+        let f: Vec<&str> = line.splitn(6, '\t').collect();
+        if f.len() != 6 {
+            return None;
+        }
+
+        let status = match f[2] {
+            "pending" => Status::Pending,
+            "expired" => Status::Expired,
+            s => match s.strip_prefix("acknowledged ") {
+                Some(t) => {
+                    Status::Acknowledged(NaiveDateTime::parse_from_str(t, RECEIPT_FMT).ok()?)
+                }
+                None => return None,
+            },
+        };
+
+        Some(Receipt {
+            at: NaiveDateTime::parse_from_str(f[0], RECEIPT_FMT).ok()?,
+            receipt: f[1].to_string(),
+            status,
+            source: f[3].to_string(),
+            line: f[4].parse().ok()?,
+            text: f[5].to_string(),
+        })
+    }
+
+    fn to_line(&self) -> String {
+        let status = match self.status {
+            Status::Pending => "pending".to_string(),
+            Status::Expired => "expired".to_string(),
+            Status::Acknowledged(t) => format!("acknowledged {}", t.format(RECEIPT_FMT)),
+        };
+
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\n",
+            self.at.format(RECEIPT_FMT),
+            clean(&self.receipt),
+            status,
+            clean(&self.source),
+            self.line,
+            clean(&self.text)
+        )
+    }
+}
+
+/*
+ * Open the receipts file's lock file ("<receipts file>.lock") and wait for
+ * an exclusive lock on it. The lock is held until the file is dropped so
+ * one run can't lose a receipt another adds while rewriting the file.
+ */
+pub fn lock_receipts(file: &str) -> Result<File> {
+    let path = format!("{}.lock", file);
+    let rc = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .and_then(|f| f.lock().map(|_| f));
+
+    match rc {
+        Ok(f) => Ok(f),
+        Err(e) => Err(format!("failed to lock receipts file {} ({})", path, e).into()),
+    }
+}
+
+/* Load the receipts file. A missing file has no receipts. */
+pub fn load_receipts(file: &str) -> Result<Vec<Receipt>> {
+    match fs::read_to_string(file) {
+        Ok(s) => Ok(s.lines().filter_map(Receipt::parse).collect()),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("failed to read receipts file {} ({})", file, e).into()),
+    }
+}
+
+/* Append a new receipt to the receipts file. */
+pub fn add_receipt(file: &str, r: &Receipt) -> Result<()> {
+    let _lock = lock_receipts(file)?;
+    let mut f = OpenOptions::new().create(true).append(true).open(file)?;
+    f.write_all(r.to_line().as_bytes())?;
+    Ok(())
+}
+
+/*
+ * Rewrite the receipts file, forgetting finished receipts older than
+ * RECEIPTS_KEEP_DAYS before now. The caller holds the lock from when the
+ * receipts were loaded.
+ */
+pub fn save_receipts(file: &str, receipts: &[Receipt], now: NaiveDateTime) -> Result<()> {
+    let oldest = now - Duration::days(RECEIPTS_KEEP_DAYS);
+    let s: String = receipts
+        .iter()
+        .filter(|r| r.status == Status::Pending || r.at >= oldest)
+        .map(Receipt::to_line)
+        .collect();
+
+    fs::write(file, s)?;
+    Ok(())
+}
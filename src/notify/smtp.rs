@@ -0,0 +1,134 @@
+use crate::config::{get_int, get_list, get_str};
+use crate::notify::{require, Notification, Notifier};
+use crate::Result;
+use lettre::smtp::authentication::Credentials;
+use lettre::{ClientSecurity, ClientTlsParameters, SmtpClient, Transport};
+use lettre_email::EmailBuilder;
+use native_tls::TlsConnector;
+use yaml_rust::Yaml;
+
+/* How the connection to the SMTP server is secured. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Security {
+    None,
+    StartTls,
+    Tls,
+}
+
+impl Security {
+    fn default_port(self) -> u16 {
+        match self {
+            Security::None => 25,
+            Security::StartTls => 587,
+            Security::Tls => 465,
+        }
+    }
+}
+
+/*
+ * Email the notification via an SMTP server.
+ *
+ *   - type: smtp
+ *     host: <host>
+ *     port: <port>                 (default 587, 465 for tls, 25 for none)
+ *     security: <starttls|tls|none> (default starttls)
+ *     username: <username>         (optional)
+ *     password: <password>         (optional)
+ *     from: <address>
+ *     to: <address,...>
+ */
+pub struct Smtp {
+    host: String,
+    port: u16,
+    security: Security,
+    credentials: Option<(String, String)>,
+    from: String,
+    to: Vec<String>,
+}
+
+impl Smtp {
+    pub fn from_config(cfg: &Yaml) -> Result<Smtp> {
+        let security = match get_str(cfg, "security").unwrap_or("starttls") {
+            "none" => Security::None,
+            "starttls" => Security::StartTls,
+            "tls" => Security::Tls,
+            s => return Err(format!("invalid smtp security ({})", s).into()),
+        };
+
+        let port = match get_int(cfg, "port") {
+            Some(p) if p > 0 && p <= i64::from(u16::MAX) => p as u16,
+            Some(p) => return Err(format!("invalid smtp port ({})", p).into()),
+            None => security.default_port(),
+        };
+
+        let credentials = match (get_str(cfg, "username"), get_str(cfg, "password")) {
+            (Some(u), Some(p)) => Some((u.to_string(), p.to_string())),
+            (None, None) => None,
+            _ => return Err("smtp channel requires both 'username' and 'password'".into()),
+        };
+
+        let to = get_list(cfg, "to");
+        if to.is_empty() {
+            return Err("smtp channel requires 'to'".into());
+        }
+
+        Ok(Smtp {
+            host: require(cfg, "smtp", "host")?.to_string(),
+            port,
+            security,
+            credentials,
+            from: require(cfg, "smtp", "from")?.to_string(),
+            to,
+        })
+    }
+
+    fn client(&self) -> Result<SmtpClient> {
+        let security = match self.security {
+            Security::None => ClientSecurity::None,
+            s => {
+                let tls = ClientTlsParameters::new(self.host.clone(), TlsConnector::new()?);
+                match s {
+                    Security::Tls => ClientSecurity::Wrapper(tls),
+                    _ => ClientSecurity::Required(tls),
+                }
+            }
+        };
+
+        let mut client = SmtpClient::new((self.host.as_str(), self.port), security)?;
+        if let Some((u, p)) = &self.credentials {
+            client = client.credentials(Credentials::new(u.clone(), p.clone()));
+        }
+
+        Ok(client)
+    }
+}
+
+impl Notifier for Smtp {
+    fn notify(&self, n: &Notification) -> Result<()> {
+        println!("smtp: {:?} \"{}\"", n.at, n.text);
+
+        let when = n.at.format("%a %Y/%m/%d %H:%M");
+        let mut body = format!("{}\n\nScheduled for {}\n", n.text, when);
+        if !n.source.is_empty() {
+            body.push_str(&format!("From {}:{}\n", n.source, n.line));
+        }
+
+        let mut email = EmailBuilder::new()
+            .from(self.from.as_str())
+            .subject(match &n.title {
+                Some(t) => format!("{} ({})", t, when),
+                None => format!("Reminder: {} ({})", n.text, when),
+            })
+            .text(body);
+        for to in &self.to {
+            email = email.to(to.as_str());
+        }
+
+        let mut transport = self.client()?.transport();
+        let rc = transport.send(email.build()?.into());
+        transport.close();
+        rc?;
+
+        Ok(())
+    }
+}
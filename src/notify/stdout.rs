@@ -0,0 +1,12 @@
+use crate::notify::{Notification, Notifier};
+use crate::Result;
+
+/* Print the notification to stdout. */
+pub struct Stdout;
+
+impl Notifier for Stdout {
+    fn notify(&self, n: &Notification) -> Result<()> {
+        println!("reminder: {:?} \"{}\"", n.at, n.full_text());
+        Ok(())
+    }
+}
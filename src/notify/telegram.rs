@@ -0,0 +1,63 @@
+use crate::config::{get_int, get_str};
+use crate::notify::{http_error, require, Notification, Notifier};
+use crate::Result;
+use serde_json::{json, Value};
+use yaml_rust::Yaml;
+
+/* The Bot API used when no `api_url:` is given. */
+pub const DEFAULT_TELEGRAM_API: &str = "https://api.telegram.org";
+
+/*
+ * Send the notification to a Telegram chat via a bot's sendMessage.
+ *
+ *   - type: telegram
+ *     bot_token: <token>
+ *     chat_id: <chat_id>
+ *     api_url: <url>          (default https://api.telegram.org)
+ */
+pub struct Telegram {
+    url: String,
+    chat_id: Value,
+}
+
+impl Telegram {
+    pub fn from_config(cfg: &Yaml) -> Result<Telegram> {
+        let api = get_str(cfg, "api_url").unwrap_or(DEFAULT_TELEGRAM_API);
+        let token = require(cfg, "telegram", "bot_token")?;
+
+        /* a numeric ID or an @channelusername */
+        let chat_id = match get_int(cfg, "chat_id") {
+            Some(id) => json!(id),
+            None => json!(require(cfg, "telegram", "chat_id")?),
+        };
+
+        Ok(Telegram {
+            url: format!("{}/bot{}/sendMessage", api.trim_end_matches('/'), token),
+            chat_id,
+        })
+    }
+}
+
+impl Notifier for Telegram {
+    fn notify(&self, n: &Notification) -> Result<()> {
+        println!("telegram: {:?} \"{}\"", n.at, n.text);
+
+        let client = reqwest::Client::new();
+        let mut resp = client
+            .post(&self.url)
+            .json(&json!({ "chat_id": self.chat_id, "text": n.full_text() }))
+            .send()
+            .map_err(http_error)?;
+
+        /* telegram returns {"ok": false, "description": ...} on failure */
+        let body: Value = resp.json().unwrap_or(Value::Null);
+        if !resp.status().is_success() || body["ok"] != json!(true) {
+            let desc = body["description"]
+                .as_str()
+                .unwrap_or("no error description");
+            return Err(format!("telegram error {} ({})", resp.status(), desc).into());
+        }
+
+        Ok(())
+    }
+}
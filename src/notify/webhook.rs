@@ -0,0 +1,105 @@
+use crate::config::get_str;
+use crate::notify::{http_error, require, Notification, Notifier};
+use crate::Result;
+use reqwest::Method;
+use yaml_rust::Yaml;
+
+/* The body sent when no `body:` template is given. */
+pub const DEFAULT_WEBHOOK_BODY: &str = r#"{"text": "{{text}}"}"#;
+
+/* Escape the string so it can be placed inside a JSON string. */
+fn json_escape(s: &str) -> String {
+    let q = serde_json::Value::from(s).to_string();
+    q[1..q.len() - 1].to_string()
+}
+
+/*
+ * Send the notification to an HTTP endpoint. The body is a template where
+ * {{text}}, {{time}}, {{source}} and {{line}} are replaced by the
+ * notification's values. With `format: json` (the default) the values are
+ * JSON escaped so they can be placed inside a JSON string.
+ *
+ *   - type: webhook
+ *     url: <url>
+ *     method: <method>        (default POST)
+ *     headers:                (optional)
+ *       <header>: <value>
+ *     format: <json|text>     (default json)
+ *     body: <template>        (default {"text": "{{text}}"})
+ */
+pub struct Webhook {
+    url: String,
+    method: Method,
+    headers: Vec<(String, String)>,
+    json: bool,
+    body: String,
+}
+
+impl Webhook {
+    pub fn from_config(cfg: &Yaml) -> Result<Webhook> {
+        let method = get_str(cfg, "method").unwrap_or("POST").to_uppercase();
+        let method = match Method::from_bytes(method.as_bytes()) {
+            Ok(m) => m,
+            Err(_) => return Err(format!("invalid webhook method ({})", method).into()),
+        };
+
+        let mut headers = Vec::new();
+        if let Some(h) = cfg["headers"].as_hash() {
+            for (k, v) in h {
+                match (k.as_str(), v.as_str()) {
+                    (Some(k), Some(v)) => headers.push((k.to_string(), v.to_string())),
+                    _ => return Err("webhook headers must be strings".into()),
+                }
+            }
+        }
+
+        let json = match get_str(cfg, "format").unwrap_or("json") {
+            "json" => true,
+            "text" => false,
+            f => return Err(format!("invalid webhook format ({})", f).into()),
+        };
+
+        Ok(Webhook {
+            url: require(cfg, "webhook", "url")?.to_string(),
+            method,
+            headers,
+            json,
+            body: get_str(cfg, "body")
+                .unwrap_or(DEFAULT_WEBHOOK_BODY)
+                .to_string(),
+        })
+    }
+}
+
+impl Notifier for Webhook {
+    fn notify(&self, n: &Notification) -> Result<()> {
+        println!("webhook: {:?} \"{}\"", n.at, n.text);
+
+        let client = reqwest::Client::new();
+        let mut req = client.request(self.method.clone(), &self.url);
+
+        let has_type = self
+            .headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case("content-type"));
+        if self.json && !has_type {
+            req = req.header("Content-Type", "application/json");
+        }
+        for (k, v) in &self.headers {
+            req = req.header(k.as_str(), v.as_str());
+        }
+
+        let body = if self.json {
+            n.render(&self.body, json_escape)
+        } else {
+            n.render(&self.body, str::to_string)
+        };
+
+        req.body(body)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .map_err(http_error)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use crate::grammar::parse_schedule;
+use crate::notify::{Channel, Notification};
+use crate::schedule::Schedule;
+use crate::Result;
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+/* All reminder strings are prefixed with "/remind ..." */
+lazy_static! {
+    pub static ref REMIND_LINE: Regex = Regex::new(r"(?x)
+        ^(/(remind|rem|r)|\s*-\s\[\s\]\s+.*\s+/(remind|rem|r))(\s+|$)
+        ").unwrap();
+
+    /* /remind <schedule> <text> */
+    static ref R_PLAIN: Regex = Regex::new(r"(?x)
+        ^/(remind|rem|r)
+        (\s+(?P<spec>.*))?$
+        ").unwrap();
+
+    /* - [ ] <text> /remind <schedule> */
+    static ref R_TASK: Regex = Regex::new(r"(?x)
+        ^\s*-\s\[\s\]\s+(?P<txt>.*)\s+/(remind|rem|r)
+        (\s+(?P<spec>.*?))?
+        \s*$
+        ").unwrap();
+
+    /* !key=value or !key="some value" */
+    static ref OPTION: Regex = Regex::new(r#"(?x)
+        (^|\s+)!(?P<key>[a-z_]+)=("(?P<quoted>[^"]*)"|(?P<value>\S+))
+        "#).unwrap();
+}
+
+/* Return what's left of the string after skipping the first n words. */
+fn skip_words(s: &str, n: usize) -> &str {
+    let mut rest = s.trim_start();
+    for _ in 0..n {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        rest = rest[end..].trim_start();
+    }
+
+    rest
+}
+
+/*
+ * Split the inline `!key=value` options out of the reminder text returning
+ * the remaining text and the options in the order given.
+ */
+fn split_options(text: &str) -> (String, Vec<(String, String)>) {
+    let options = OPTION
+        .captures_iter(text)
+        .map(|c| {
+            let v = c.name("quoted").or_else(|| c.name("value")).unwrap();
+            (c["key"].to_string(), v.as_str().to_string())
+        })
+        .collect();
+
+    (OPTION.replace_all(text, "").trim().to_string(), options)
+}
+
+/*
+ * A single reminder parsed from a line of text. The source and line number
+ * identify where the reminder came from (line numbers start at 1).
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reminder {
+    pub text: String,
+    pub schedule: Schedule,
+    pub options: Vec<(String, String)>,
+    pub task: bool,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Reminder {
+    /*
+     * Parse a reminder string. Returns None if the line isn't a reminder
+     * and an error if it is one but the schedule is invalid.
+     */
+    pub fn parse(line: &str) -> Result<Option<Reminder>> {
+        if !REMIND_LINE.is_match(line) {
+            return Ok(None);
+        }
+
+        let (text, schedule, task) = if let Some(c) = R_PLAIN.captures(line) {
+            let spec = c.name("spec").map_or("", |s| s.as_str());
+            let words: Vec<&str> = spec.split_whitespace().collect();
+            let (schedule, n) = parse_schedule(&words, false)?;
+            (skip_words(spec, n).to_string(), schedule, false)
+        } else if let Some(c) = R_TASK.captures(line) {
+            let spec = c.name("spec").map_or("", |s| s.as_str());
+            let words: Vec<&str> = spec.split_whitespace().collect();
+            let (schedule, _) = parse_schedule(&words, true)?;
+            (c["txt"].to_string(), schedule, true)
+        } else {
+            return Err("malformed reminder".into());
+        };
+
+        let (text, options) = split_options(&text);
+
+        Ok(Some(Reminder {
+            text,
+            schedule,
+            options,
+            task,
+            source: String::new(),
+            line: 0,
+        }))
+    }
+
+    /*
+     * A string that identifies the reminder independent of where it sits in
+     * its source (i.e. lines can be added or removed around it).
+     */
+    pub fn identity(&self) -> String {
+        format!("{}: {} - {}", self.source, self.schedule, self.text)
+    }
+
+    /* Return true if the reminder fires at the given minute. */
+    pub fn is_due(&self, dt: NaiveDateTime) -> bool {
+        self.schedule.matches(dt)
+    }
+}
+
+/* A single time a reminder fires. */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occurrence<'a> {
+    pub at: NaiveDateTime,
+    pub reminder: &'a Reminder,
+}
+
+/*
+ * Evaluate the reminders across a range of time returning every occurrence
+ * between from and to (inclusive) ordered by time.
+ */
+pub fn due_between(
+    reminders: &[Reminder],
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Vec<Occurrence<'_>> {
+    let mut v: Vec<Occurrence> = reminders
+        .iter()
+        .flat_map(|r| {
+            r.schedule
+                .occurrences(from, to)
+                .into_iter()
+                .map(move |at| Occurrence { at, reminder: r })
+        })
+        .collect();
+
+    v.sort_by_key(|o| o.at);
+    v
+}
+
+/*
+ * Parse the reminder line. If the reminder is due at the given time then
+ * send a notification message on every channel (from load_channels()). The
+ * same channels should be passed to every call so their rate limits hold.
+ */
+pub fn check_reminder(channels: &[Channel], dt: NaiveDateTime, r_str: &str) -> Result<()> {
+    if let Some(r) = Reminder::parse(r_str)? {
+        if r.is_due(dt) {
+            let n = Notification::from_reminder(&r, dt);
+            for ch in channels.iter().filter(|ch| !ch.fallback_only) {
+                ch.notify(&n)?;
+            }
+        }
+    }
+
+    Ok(())
+}
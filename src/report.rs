@@ -0,0 +1,40 @@
+use std::fmt;
+
+/* The exit status used when a run completes but something failed. */
+pub const EXIT_FAILURES: i32 = 2;
+
+/*
+ * Failures collected during a run. A bad reminder or a failed notification
+ * is recorded here instead of aborting so every other reminder is still
+ * evaluated and delivered.
+ */
+#[derive(Debug, Default)]
+pub struct Report {
+    pub errors: Vec<String>,
+}
+
+impl Report {
+    pub fn new() -> Report {
+        Report::default()
+    }
+
+    pub fn add<E: fmt::Display>(&mut self, context: &str, e: E) {
+        self.errors.push(format!("{}: {}", context, e));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /* Print the failures to stderr. */
+    pub fn print(&self) {
+        if self.errors.is_empty() {
+            return;
+        }
+
+        eprintln!("{} error(s):", self.errors.len());
+        for e in &self.errors {
+            eprintln!("  {}", e);
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::briefing::{Briefing, BRIEFING_ID};
+use crate::config::get_bool;
+use crate::ledger::Ledger;
+use crate::notify::{digest, Channel, Notification};
+use crate::reminder::{due_between, Reminder};
+use crate::report::Report;
+use chrono::{Duration, NaiveDateTime};
+use std::thread;
+use yaml_rust::Yaml;
+
+/*
+ * Group the notifications due at once on a channel into what it's sent,
+ * each batch being indexes into ns. A batch of one is sent as is and a
+ * larger one as a digest (see batch_notification()). A channel with a
+ * digest threshold gets a digest when more than that many are due, except
+ * for notifications with their own message options (e.g. a priority) which
+ * are still sent singly. A fallback only channel gets nothing.
+ */
+pub fn batches(ch: &Channel, ns: &[&Notification]) -> Vec<Vec<usize>> {
+    if ch.fallback_only {
+        return Vec::new();
+    }
+
+    match ch.digest {
+        Some(t) if ns.len() > t => {
+            let (singly, rest): (Vec<usize>, Vec<usize>) =
+                (0..ns.len()).partition(|&i| ch.singly(ns[i]));
+            singly
+                .into_iter()
+                .map(|i| vec![i])
+                .chain(Some(rest).filter(|r| !r.is_empty()))
+                .collect()
+        }
+        _ => (0..ns.len()).map(|i| vec![i]).collect(),
+    }
+}
+
+/* The notification sent for a batch from batches(). */
+pub fn batch_notification(ns: &[&Notification], batch: &[usize]) -> Notification {
+    match batch {
+        [i] => ns[*i].clone(),
+        _ => digest(&batch.iter().map(|&i| ns[i]).collect::<Vec<_>>()),
+    }
+}
+
+/*
+ * Evaluate every reminder between from and now (inclusive) and notify the
+ * due ones on every channel. Occurrences before now are late (i.e. missed
+ * while the machine was off) and are marked as such unless
+ * `catchup_mark_late: false`. With a ledger anything already delivered to a
+ * channel is skipped. Each channel is sent its notifications in order (rate
+ * limited if configured) in its own thread so a slow channel doesn't hold
+ * up the others, batched as batches() decides. Each occurrence and channel
+ * is delivered independently with any failures added to the report.
+ * Returns the earliest occurrence that failed to deliver, if any.
+ */
+pub fn deliver(
+    cfg: &Yaml,
+    channels: &[Channel],
+    reminders: &[Reminder],
+    from: NaiveDateTime,
+    now: NaiveDateTime,
+    ledger: &mut Option<Ledger>,
+    report: &mut Report,
+) -> Option<NaiveDateTime> {
+    let mark_late = get_bool(cfg, "catchup_mark_late").unwrap_or(true);
+
+    let due: Vec<(String, Notification)> = due_between(reminders, from, now)
+        .into_iter()
+        .map(|o| {
+            let mut n = Notification::from_reminder(o.reminder, o.at);
+            if o.at < now && mark_late {
+                n.text = format!("{} (late)", n.text);
+                n.late = true;
+            }
+            (o.reminder.identity(), n)
+        })
+        .collect();
+
+    /* the notifications each channel still needs */
+    let pending: Vec<Vec<usize>> = channels
+        .iter()
+        .map(|ch| {
+            (0..due.len())
+                .filter(|&i| {
+                    let (id, n) = &due[i];
+                    ledger
+                        .as_ref()
+                        .is_none_or(|l| !l.contains(id, n.at, &ch.name))
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut sent: Vec<(usize, usize, std::result::Result<(), String>)> = thread::scope(|s| {
+        let due = &due;
+        let handles: Vec<_> = channels
+            .iter()
+            .zip(&pending)
+            .enumerate()
+            .map(|(c, (ch, idx))| {
+                s.spawn(move || {
+                    let ns: Vec<&Notification> = idx.iter().map(|&i| &due[i].1).collect();
+                    batches(ch, &ns)
+                        .into_iter()
+                        .flat_map(|b| {
+                            let n = batch_notification(&ns, &b);
+                            let rc = ch.notify(&n).map_err(|e| e.to_string());
+                            b.into_iter()
+                                .map(|j| (idx[j], c, rc.clone()))
+                                .collect::<Vec<_>>()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("channel thread panicked"))
+            .collect()
+    });
+
+    /* report in occurrence then channel order */
+    sent.sort_by_key(|(i, c, _)| (*i, *c));
+
+    let mut failed = None;
+    for (i, c, rc) in sent {
+        let (id, n) = &due[i];
+        let ch = &channels[c];
+        let context = format!("{}:{} ({})", n.source, n.line, ch.name);
+        match (rc, ledger.as_mut()) {
+            (Err(e), _) => {
+                failed = failed.or(Some(n.at));
+                report.add(&context, e);
+            }
+            (Ok(()), Some(l)) => {
+                if let Err(e) = l.record(id, n.at, &ch.name) {
+                    report.add(&context, e);
+                }
+            }
+            (Ok(()), None) => (),
+        }
+    }
+
+    failed
+}
+
+/*
+ * The last minute to save as evaluated after delivering up to now. With a
+ * ledger this stops short of the earliest failed occurrence so the next run
+ * retries it (the ledger keeps everything that was delivered from being
+ * sent again). Without one a failure isn't retried.
+ */
+pub fn evaluated_until(
+    now: NaiveDateTime,
+    failed: Option<NaiveDateTime>,
+    ledger: &Option<Ledger>,
+) -> NaiveDateTime {
+    match (failed, ledger) {
+        (Some(f), Some(_)) => (f - Duration::minutes(1)).min(now),
+        _ => now,
+    }
+}
+
+/*
+ * Send the daily briefing if it was due between from and now (inclusive).
+ * When catching up over several days only the most recent is sent. As with
+ * reminders the ledger keeps a channel from being sent the same one twice.
+ * Returns the briefing's time if it failed to deliver to any channel.
+ */
+pub fn brief(
+    briefing: &Briefing,
+    channels: &[Channel],
+    reminders: &[Reminder],
+    from: NaiveDateTime,
+    now: NaiveDateTime,
+    ledger: &mut Option<Ledger>,
+    report: &mut Report,
+) -> Option<NaiveDateTime> {
+    let at = briefing.schedule.occurrences(from, now).pop()?;
+    let n = briefing.notification(reminders, at, now);
+
+    for name in &briefing.channels {
+        if !channels.iter().any(|ch| &ch.name == name) {
+            report.add(BRIEFING_ID, format!("unknown briefing channel ({})", name));
+        }
+    }
+
+    let mut failed = None;
+    for ch in briefing.recipients(channels) {
+        if ledger
+            .as_ref()
+            .is_some_and(|l| l.contains(BRIEFING_ID, at, &ch.name))
+        {
+            continue;
+        }
+
+        let context = format!("{} ({})", BRIEFING_ID, ch.name);
+        match (ch.notify(&n), ledger.as_mut()) {
+            (Err(e), _) => {
+                failed = Some(at);
+                report.add(&context, e);
+            }
+            (Ok(()), Some(l)) => {
+                if let Err(e) = l.record(BRIEFING_ID, at, &ch.name) {
+                    report.add(&context, e);
+                }
+            }
+            (Ok(()), None) => (),
+        }
+    }
+
+    failed
+}
+
+/*
+ * Check on earlier notifications (e.g. Pushover emergency receipts) and
+ * send any that went unacknowledged to their fallback channel.
+ */
+pub fn follow_up(channels: &[Channel], report: &mut Report) {
+    let mut fallbacks = Vec::new();
+    for ch in channels {
+        if let Err(e) = ch.follow_up(&mut fallbacks) {
+            report.add(&ch.name, e);
+        }
+    }
+
+    for f in fallbacks {
+        let n = &f.notification;
+        let context = format!("{}:{} ({})", n.source, n.line, f.channel);
+        match channels.iter().find(|ch| ch.name == f.channel) {
+            Some(ch) => {
+                if let Err(e) = ch.notify(n) {
+                    report.add(&context, e);
+                }
+            }
+            None => report.add(
+                &context,
+                format!("unknown fallback channel ({})", f.channel),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::retention;
+    use crate::notify::Stdout;
+    use crate::state::{catchup_max, catchup_start};
+    use chrono::NaiveDate;
+    use std::{env, fs, process};
+    use yaml_rust::YamlLoader;
+
+    fn dt(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    fn channel(digest: Option<usize>) -> Channel {
+        let mut ch = Channel::new("stdout", Box::new(Stdout));
+        ch.digest = digest;
+        ch
+    }
+
+    fn notifications(texts: &[&str]) -> Vec<Notification> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let mut n = Notification::new(t, dt(2020, 4, 29, 11, i as u32));
+                if t.starts_with('!') {
+                    n.options.push(("priority".to_string(), "1".to_string()));
+                }
+                n
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batches_digest_threshold() {
+        let ns = notifications(&["a", "b", "c"]);
+        let ns: Vec<&Notification> = ns.iter().collect();
+
+        let singles = vec![vec![0], vec![1], vec![2]];
+        assert_eq!(batches(&channel(None), &ns), singles);
+        assert_eq!(batches(&channel(Some(3)), &ns), singles);
+        assert_eq!(batches(&channel(Some(2)), &ns), vec![vec![0, 1, 2]]);
+
+        let mut ch = channel(Some(2));
+        ch.fallback_only = true;
+        assert!(batches(&ch, &ns).is_empty());
+
+        let d = batch_notification(&ns, &[0, 1, 2]);
+        assert_eq!(d.title.as_deref(), Some("3 reminders"));
+        assert_eq!(d.text, "- a\n- b\n- c");
+        assert_eq!(d.at, dt(2020, 4, 29, 11, 2));
+    }
+
+    #[test]
+    fn batches_send_options_singly() {
+        let ns = notifications(&["!a", "b", "!c", "d"]);
+        let ns: Vec<&Notification> = ns.iter().collect();
+        assert_eq!(
+            batches(&channel(Some(1)), &ns),
+            vec![vec![0], vec![2], vec![1, 3]]
+        );
+
+        /* a single one left over isn't sent as a digest */
+        let ns = notifications(&["!a", "b", "!c"]);
+        let ns: Vec<&Notification> = ns.iter().collect();
+        let b = batches(&channel(Some(2)), &ns);
+        assert_eq!(b, vec![vec![0], vec![2], vec![1]]);
+        assert_eq!(batch_notification(&ns, &b[2]), *ns[1]);
+
+        let ns = notifications(&["!a", "!b", "!c"]);
+        let ns: Vec<&Notification> = ns.iter().collect();
+        assert_eq!(
+            batches(&channel(Some(1)), &ns),
+            vec![vec![0], vec![1], vec![2]]
+        );
+    }
+
+    #[test]
+    fn evaluated_until_failure() {
+        let now = dt(2020, 4, 29, 11, 0);
+        let ledger = Some(Ledger::default());
+
+        assert_eq!(evaluated_until(now, None, &ledger), now);
+        assert_eq!(evaluated_until(now, Some(now), &None), now);
+        assert_eq!(
+            evaluated_until(now, Some(dt(2020, 4, 29, 10, 30)), &ledger),
+            dt(2020, 4, 29, 10, 29)
+        );
+        assert_eq!(
+            evaluated_until(now, Some(now), &ledger),
+            dt(2020, 4, 29, 10, 59)
+        );
+    }
+
+    /*
+     * A failure is retried for up to `catchup_max:` and the ledger has to
+     * remember the channels that did get it for as long.
+     */
+    #[test]
+    fn retry_within_retention() {
+        let cfg = YamlLoader::load_from_str("catchup_max: 20160")
+            .unwrap()
+            .remove(0);
+        let file = env::temp_dir().join(format!("reminders-retry-{}.ledger", process::id()));
+        let file = file.to_string_lossy().to_string();
+        let _ = fs::remove_file(&file);
+
+        /* delivered to one channel but not another */
+        let failed = dt(2020, 4, 1, 11, 0);
+        let mut ledger = Some(Ledger::load(&file, failed, retention(&cfg)).unwrap());
+        if let Some(l) = &mut ledger {
+            l.record("/remind 11am x", failed, "phone").unwrap();
+        }
+        let last = evaluated_until(failed, Some(failed), &ledger);
+        drop(ledger);
+
+        /* ten days later the retry still starts at the failure */
+        let now = dt(2020, 4, 11, 11, 0);
+        let from = catchup_start(Some(last), now, catchup_max(&cfg).unwrap());
+        assert_eq!(from, failed);
+
+        let ledger = Ledger::load(&file, now, retention(&cfg)).unwrap();
+        assert!(ledger.contains("/remind 11am x", failed, "phone"));
+        drop(ledger);
+
+        let _ = fs::remove_file(&file);
+        let _ = fs::remove_file(format!("{}.lock", file));
+    }
+}
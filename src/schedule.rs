@@ -0,0 +1,248 @@
+use chrono::prelude::*;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use std::fmt;
+
+/*
+ * When a reminder fires. All schedules are evaluated at minute resolution
+ * against the local time.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    /* <MM>/<DD>[/<YY>|/<YYYY>] - a date (the current year if none) @ <time> */
+    Date {
+        year: Option<i32>,
+        month: u32,
+        day: u32,
+        time: NaiveTime,
+    },
+    /* <sun|mon|...> or weekly - every <weekday> @ <time> */
+    Weekly {
+        weekday: Weekday,
+        time: NaiveTime,
+    },
+    /* <HH>:<MM> or daily - every day @ <time> */
+    Daily {
+        time: NaiveTime,
+    },
+    /* monthly - the 1st of every month @ <time> */
+    Monthly {
+        time: NaiveTime,
+    },
+    /* biweekly - every other <weekday> @ <time> (even ISO weeks) */
+    Biweekly {
+        weekday: Weekday,
+        time: NaiveTime,
+    },
+}
+
+/* The time of day used when a reminder doesn't specify one. */
+pub fn default_time() -> NaiveTime {
+    NaiveTime::from_hms(8, 0, 0)
+}
+
+impl Schedule {
+    /* The time of day the schedule fires. */
+    pub fn time(&self) -> NaiveTime {
+        match *self {
+            Schedule::Date { time, .. }
+            | Schedule::Weekly { time, .. }
+            | Schedule::Daily { time }
+            | Schedule::Monthly { time }
+            | Schedule::Biweekly { time, .. } => time,
+        }
+    }
+
+    /* Return true if the schedule fires at some point on the given date. */
+    pub fn matches_date(&self, d: NaiveDate) -> bool {
+        match *self {
+            Schedule::Date {
+                year, month, day, ..
+            } => {
+                year.unwrap_or_else(|| d.year()) == d.year() && month == d.month() && day == d.day()
+            }
+            Schedule::Weekly { weekday, .. } => d.weekday() == weekday,
+            Schedule::Daily { .. } => true,
+            Schedule::Monthly { .. } => d.day() == 1,
+            Schedule::Biweekly { weekday, .. } => {
+                d.weekday() == weekday && d.iso_week().week().is_multiple_of(2)
+            }
+        }
+    }
+
+    /* Return true if the schedule fires at the given minute. */
+    pub fn matches(&self, dt: NaiveDateTime) -> bool {
+        self.matches_date(dt.date()) && dt.time() == self.time()
+    }
+
+    /* The first time the schedule fires after the given time, if ever. */
+    pub fn next_after(&self, dt: NaiveDateTime) -> Option<NaiveDateTime> {
+        match *self {
+            Schedule::Date {
+                year: Some(y),
+                month,
+                day,
+                time,
+            } => NaiveDate::from_ymd_opt(y, month, day)
+                .map(|d| d.and_time(time))
+                .filter(|t| *t > dt),
+            /* every year, 8 years covers a 2/29 across a skipped leap year */
+            Schedule::Date {
+                year: None,
+                month,
+                day,
+                time,
+            } => (dt.year()..=(dt.year() + 8))
+                .filter_map(|y| NaiveDate::from_ymd_opt(y, month, day))
+                .map(|d| d.and_time(time))
+                .find(|t| *t > dt),
+            _ => (0..=366)
+                .map(|i| dt.date() + Duration::days(i))
+                .filter(|d| self.matches_date(*d))
+                .map(|d| d.and_time(self.time()))
+                .find(|t| *t > dt),
+        }
+    }
+
+    /* All the times the schedule fires between from and to (inclusive). */
+    pub fn occurrences(&self, from: NaiveDateTime, to: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let mut v = Vec::new();
+        let mut d = from.date();
+
+        while d <= to.date() {
+            let dt = d.and_time(self.time());
+            if dt >= from && dt <= to && self.matches_date(d) {
+                v.push(dt);
+            }
+            d = d.succ();
+        }
+
+        v
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Schedule::Date {
+                year: Some(y),
+                month,
+                day,
+                time,
+            } => write!(
+                f,
+                "{:04}/{:02}/{:02} @ {}",
+                y,
+                month,
+                day,
+                time.format("%H:%M")
+            ),
+            Schedule::Date {
+                year: None,
+                month,
+                day,
+                time,
+            } => write!(
+                f,
+                "every {:02}/{:02} @ {}",
+                month,
+                day,
+                time.format("%H:%M")
+            ),
+            Schedule::Weekly { weekday, time } => {
+                write!(f, "every {} @ {}", weekday, time.format("%H:%M"))
+            }
+            Schedule::Daily { time } => write!(f, "every day @ {}", time.format("%H:%M")),
+            Schedule::Monthly { time } => {
+                write!(f, "the 1st of every month @ {}", time.format("%H:%M"))
+            }
+            Schedule::Biweekly { weekday, time } => write!(
+                f,
+                "every other {} @ {} (even weeks)",
+                weekday,
+                time.format("%H:%M")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    #[test]
+    fn next_after_biweekly() {
+        let s = Schedule::Biweekly {
+            weekday: Weekday::Mon,
+            time: default_time(),
+        };
+
+        /* 2020/04/27 is in ISO week 18, 2020/05/04 in week 19 */
+        assert_eq!(
+            s.next_after(dt(2020, 4, 29, 12, 0)),
+            Some(dt(2020, 5, 11, 8, 0))
+        );
+        assert_eq!(
+            s.next_after(dt(2020, 4, 27, 7, 59)),
+            Some(dt(2020, 4, 27, 8, 0))
+        );
+        assert_eq!(
+            s.next_after(dt(2020, 4, 27, 8, 0)),
+            Some(dt(2020, 5, 11, 8, 0))
+        );
+    }
+
+    #[test]
+    fn next_after_date() {
+        let once = Schedule::Date {
+            year: Some(2020),
+            month: 4,
+            day: 29,
+            time: NaiveTime::from_hms(11, 0, 0),
+        };
+        assert_eq!(
+            once.next_after(dt(2020, 4, 29, 10, 59)),
+            Some(dt(2020, 4, 29, 11, 0))
+        );
+        assert_eq!(once.next_after(dt(2020, 4, 29, 11, 0)), None);
+
+        let yearly = Schedule::Date {
+            year: None,
+            month: 4,
+            day: 29,
+            time: NaiveTime::from_hms(11, 0, 0),
+        };
+        assert_eq!(
+            yearly.next_after(dt(2020, 4, 29, 11, 0)),
+            Some(dt(2021, 4, 29, 11, 0))
+        );
+
+        let leap = Schedule::Date {
+            year: None,
+            month: 2,
+            day: 29,
+            time: default_time(),
+        };
+        assert_eq!(
+            leap.next_after(dt(2021, 3, 1, 0, 0)),
+            Some(dt(2024, 2, 29, 8, 0))
+        );
+    }
+
+    #[test]
+    fn occurrences_are_inclusive() {
+        let s = Schedule::Daily {
+            time: default_time(),
+        };
+        assert_eq!(
+            s.occurrences(dt(2020, 4, 29, 8, 0), dt(2020, 4, 30, 8, 0)),
+            vec![dt(2020, 4, 29, 8, 0), dt(2020, 4, 30, 8, 0)]
+        );
+        assert!(s
+            .occurrences(dt(2020, 4, 29, 8, 1), dt(2020, 4, 30, 7, 59))
+            .is_empty());
+    }
+}
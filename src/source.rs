@@ -0,0 +1,159 @@
+use crate::config::get_str;
+use crate::reminder::Reminder;
+use crate::report::Report;
+use crate::Result;
+use std::fs;
+use yaml_rust::Yaml;
+
+/*
+ * A blob of text that may contain reminder strings along with a name
+ * describing where it came from (a file path, URL or "config").
+ */
+#[derive(Clone, Debug)]
+pub struct Source {
+    pub name: String,
+    pub text: String,
+}
+
+impl Source {
+    /*
+     * Parse every reminder string in the source returning the line number,
+     * the line itself and the reminder or why it's invalid. Lines that
+     * aren't reminders are skipped.
+     */
+    pub fn parse(&self) -> Vec<(usize, &str, Result<Reminder>)> {
+        let mut v = Vec::new();
+
+        for (i, line) in self.text.lines().enumerate() {
+            match Reminder::parse(line) {
+                Ok(None) => continue,
+                Ok(Some(mut r)) => {
+                    r.source = self.name.clone();
+                    r.line = i + 1;
+                    v.push((i + 1, line, Ok(r)));
+                }
+                Err(e) => v.push((i + 1, line, Err(e))),
+            }
+        }
+
+        v
+    }
+
+    /*
+     * Parse every reminder string in the source. Lines that aren't reminders
+     * are skipped, lines that are reminders but fail to parse are returned as
+     * errors.
+     */
+    pub fn reminders(&self) -> Vec<Result<Reminder>> {
+        self.parse()
+            .into_iter()
+            .map(|(n, _, r)| r.map_err(|e| format!("{}:{}: {}", self.name, n, e).into()))
+            .collect()
+    }
+}
+
+/*
+ * Get the specified file that contains the reminder strings. This function
+ * will fetch the file over HTTP (w/ basic auth if specified) or read the
+ * file directory from local disk.
+ */
+pub fn get_todo(cfg: &Yaml) -> Result<Option<Source>> {
+    let file = match get_str(cfg, "file") {
+        Some(f) => f,
+        None => {
+            if cfg["reminders"].is_badvalue() {
+                return Err("invalid config file".into());
+            }
+            return Ok(None);
+        }
+    };
+
+    let txt = if file.starts_with("http") {
+        let client = reqwest::Client::new();
+
+        if get_str(cfg, "http_auth") == Some("basic") {
+            let (user, pass) = match (get_str(cfg, "http_username"), get_str(cfg, "http_password"))
+            {
+                (Some(u), Some(p)) => (u, p),
+                _ => return Err("invalid http credentials".into()),
+            };
+
+            client
+                .get(file)
+                .basic_auth(user, Some(pass))
+                .send()?
+                .text()?
+        } else {
+            client.get(file).send()?.text()?
+        }
+    } else {
+        match fs::read_to_string(file) {
+            Ok(t) => t,
+            Err(e) => return Err(format!("failed to read reminder file {} ({})", file, e).into()),
+        }
+    };
+
+    //println!("{:?}", txt);
+    Ok(Some(Source {
+        name: file.to_string(),
+        text: txt,
+    }))
+}
+
+/* The `reminders:` text in the config itself, if any. */
+fn config_source(cfg: &Yaml) -> Option<Source> {
+    get_str(cfg, "reminders").map(|txt| Source {
+        name: "config".to_string(),
+        text: txt.to_string(),
+    })
+}
+
+/*
+ * Load all the reminder sources specified in the config. This is the
+ * `reminders:` text in the config itself followed by the `file:`.
+ */
+pub fn load_sources(cfg: &Yaml) -> Result<Vec<Source>> {
+    let mut sources: Vec<Source> = config_source(cfg).into_iter().collect();
+
+    if let Some(s) = get_todo(cfg)? {
+        sources.push(s);
+    }
+
+    Ok(sources)
+}
+
+/*
+ * Load all the reminder sources specified in the config and parse every
+ * reminder in them. A source that fails to load or a reminder that fails
+ * to parse is added to the report and the rest are still returned.
+ */
+pub fn load_reminders(cfg: &Yaml, report: &mut Report) -> Result<Vec<Reminder>> {
+    let sources = match load_sources(cfg) {
+        Ok(s) => s,
+        Err(e) if get_str(cfg, "file").is_none() => return Err(e),
+        Err(e) => {
+            report.add("file", e);
+            config_source(cfg).into_iter().collect()
+        }
+    };
+
+    Ok(parse_sources(&sources, report))
+}
+
+/*
+ * Parse every reminder in the sources. A reminder that fails to parse is
+ * added to the report and the rest are still returned.
+ */
+pub fn parse_sources(sources: &[Source], report: &mut Report) -> Vec<Reminder> {
+    let mut reminders = Vec::new();
+    for source in sources {
+        for r in source.reminders() {
+            match r {
+                Ok(r) => reminders.push(r),
+                Err(e) => report.errors.push(e.to_string()),
+            }
+        }
+    }
+
+    reminders
+}
@@ -0,0 +1,134 @@
+use crate::config::{get_int, get_str};
+use crate::Result;
+use chrono::{Duration, NaiveDateTime};
+use std::fs;
+use std::io::ErrorKind;
+use yaml_rust::Yaml;
+
+const STATE_FMT: &str = "%Y/%m/%d %H:%M";
+
+/* The default maximum amount of time to catch up on (one day). */
+pub const DEFAULT_CATCHUP_MAX: i64 = 24 * 60;
+
+/* The most `catchup_max:` can be (a year). */
+pub const MAX_CATCHUP_MAX: i64 = 366 * 24 * 60;
+
+/*
+ * Read the last evaluated timestamp from the state file. A missing state
+ * file means this is the first run.
+ */
+pub fn load_last_run(file: &str) -> Result<Option<NaiveDateTime>> {
+    let s = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("failed to read state file {} ({})", file, e).into()),
+    };
+
+    match NaiveDateTime::parse_from_str(s.trim(), STATE_FMT) {
+        Ok(dt) => Ok(Some(dt)),
+        Err(e) => Err(format!("invalid state file {} ({})", file, e).into()),
+    }
+}
+
+/* Write the last evaluated timestamp to the state file. */
+pub fn save_last_run(file: &str, dt: NaiveDateTime) -> Result<()> {
+    if let Err(e) = fs::write(file, format!("{}\n", dt.format(STATE_FMT))) {
+        return Err(format!("failed to write state file {} ({})", file, e).into());
+    }
+
+    Ok(())
+}
+
+/*
+ * Get the first minute to evaluate given the last evaluated timestamp. Every
+ * minute after the last run is evaluated but never more than max minutes.
+ */
+pub fn catchup_start(last: Option<NaiveDateTime>, now: NaiveDateTime, max: i64) -> NaiveDateTime {
+    let earliest = now - Duration::minutes(max.clamp(0, MAX_CATCHUP_MAX));
+
+    match last {
+        Some(l) if l < now => (l + Duration::minutes(1)).max(earliest),
+        _ => now,
+    }
+}
+
+/* The config's `catchup_max:` minutes, which must be 0 to MAX_CATCHUP_MAX. */
+pub fn catchup_max(cfg: &Yaml) -> Result<i64> {
+    match get_int(cfg, "catchup_max").unwrap_or(DEFAULT_CATCHUP_MAX) {
+        m if (0..=MAX_CATCHUP_MAX).contains(&m) => Ok(m),
+        m => Err(format!(
+            "invalid catchup_max ({}, must be 0 to {})",
+            m, MAX_CATCHUP_MAX
+        )
+        .into()),
+    }
+}
+
+/*
+ * Get the first minute to evaluate for a run at now. With a `state_file:`
+ * in the config this is the minute after the last run, else just now.
+ */
+pub fn catchup_from(cfg: &Yaml, now: NaiveDateTime) -> Result<NaiveDateTime> {
+    match get_str(cfg, "state_file") {
+        Some(f) => Ok(catchup_start(load_last_run(f)?, now, catchup_max(cfg)?)),
+        None => Ok(now),
+    }
+}
+
+/* Save now as the last run if the config has a `state_file:`. */
+pub fn save_state(cfg: &Yaml, now: NaiveDateTime) -> Result<()> {
+    match get_str(cfg, "state_file") {
+        Some(f) => save_last_run(f, now),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use yaml_rust::YamlLoader;
+
+    fn dt(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    #[test]
+    fn catchup_start_limits() {
+        let now = dt(2020, 4, 29, 11, 0);
+        assert_eq!(catchup_start(None, now, 60), now);
+        assert_eq!(catchup_start(Some(now), now, 60), now);
+        assert_eq!(catchup_start(Some(dt(2020, 4, 29, 12, 0)), now, 60), now);
+        assert_eq!(
+            catchup_start(Some(dt(2020, 4, 29, 10, 30)), now, 60),
+            dt(2020, 4, 29, 10, 31)
+        );
+        assert_eq!(
+            catchup_start(Some(dt(2020, 4, 28, 10, 30)), now, 60),
+            dt(2020, 4, 29, 10, 0)
+        );
+        assert_eq!(catchup_start(Some(dt(2020, 4, 28, 10, 30)), now, 0), now);
+        assert_eq!(catchup_start(Some(dt(2020, 4, 28, 10, 30)), now, -5), now);
+
+        /* a huge max is clamped instead of overflowing */
+        let long_ago = dt(1900, 1, 1, 0, 0);
+        assert_eq!(
+            catchup_start(Some(long_ago), now, i64::MAX),
+            now - Duration::minutes(MAX_CATCHUP_MAX)
+        );
+    }
+
+    #[test]
+    fn catchup_max_range() {
+        let cfg = |s: &str| YamlLoader::load_from_str(s).unwrap().remove(0);
+        assert_eq!(catchup_max(&cfg("file: x")).unwrap(), DEFAULT_CATCHUP_MAX);
+        assert_eq!(catchup_max(&cfg("catchup_max: 0")).unwrap(), 0);
+        assert_eq!(
+            catchup_max(&cfg(&format!("catchup_max: {}", MAX_CATCHUP_MAX))).unwrap(),
+            MAX_CATCHUP_MAX
+        );
+        assert!(catchup_max(&cfg("catchup_max: -1")).is_err());
+        assert!(catchup_max(&cfg(&format!("catchup_max: {}", MAX_CATCHUP_MAX + 1))).is_err());
+        assert!(catchup_max(&cfg("catchup_max: 9223372036854775807")).is_err());
+    }
+}
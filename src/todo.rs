@@ -0,0 +1,201 @@
+use crate::Result;
+use regex::Regex;
+use std::fs;
+use std::ops::Range;
+
+lazy_static! {
+    static ref TASK_LINE: Regex = Regex::new(r"^(\s*)(-\s\[[ |x]\]\s)(.*)$").unwrap();
+    static ref HDR_LINE: Regex = Regex::new(r"^(#+)\s(.*)").unwrap();
+    static ref TAG: Regex = Regex::new(r"^#\S+$").unwrap();
+    static ref BLANK: Regex = Regex::new(r"^\s*$").unwrap();
+}
+
+/* What a single line of a markdown todo document contains. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+    Blank,
+    Header {
+        level: usize,
+        title: &'a str,
+    },
+    Task {
+        indent: &'a str,
+        done: bool,
+        text: &'a str,
+    },
+    Other,
+}
+
+/* Return true if the word is a tag (i.e. "#tag"). */
+pub fn is_tag(word: &str) -> bool {
+    TAG.is_match(word)
+}
+
+/* Classify a line of markdown. */
+pub fn parse_line(line: &str) -> Line<'_> {
+    if BLANK.is_match(line) {
+        return Line::Blank;
+    }
+
+    if let Some(caps) = HDR_LINE.captures(line) {
+        return Line::Header {
+            level: caps.get(1).unwrap().as_str().len(),
+            title: caps.get(2).unwrap().as_str(),
+        };
+    }
+
+    if let Some(caps) = TASK_LINE.captures(line) {
+        return Line::Task {
+            indent: caps.get(1).unwrap().as_str(),
+            done: caps.get(2).unwrap().as_str() == "- [x] ",
+            text: caps.get(3).unwrap().as_str(),
+        };
+    }
+
+    Line::Other
+}
+
+/* Flip a task line between "- [ ]" and "- [x]". */
+pub fn toggle_task(s: &str) -> String {
+    let check = match TASK_LINE.captures(s) {
+        None => return s.to_string(),
+        Some(caps) => caps.get(1).unwrap().as_str().len() + 3,
+    };
+
+    s.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if i == check {
+                if c == ' ' {
+                    'x'
+                } else {
+                    ' '
+                }
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/*
+ * A markdown document made up of headers (sections) and tasks. Line indexes
+ * start at 0.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct TodoDoc {
+    pub lines: Vec<String>,
+}
+
+impl TodoDoc {
+    pub fn parse(txt: &str) -> TodoDoc {
+        let mut lines: Vec<String> = txt.lines().map(|l| l.to_string()).collect();
+
+        if let Some(last) = lines.last_mut() {
+            if last.is_empty() {
+                *last = "\n".to_string(); /* insert the last newline if removed */
+            }
+        }
+
+        TodoDoc { lines }
+    }
+
+    pub fn load(file: &str) -> Result<TodoDoc> {
+        match fs::read_to_string(file) {
+            Ok(txt) => Ok(TodoDoc::parse(&txt)),
+            Err(e) => Err(format!("failed to read todo file {} ({})", file, e).into()),
+        }
+    }
+
+    pub fn save(&self, file: &str) -> Result<()> {
+        fs::write(file, self.lines.join("\n"))?;
+        Ok(())
+    }
+
+    pub fn line(&self, idx: usize) -> Line<'_> {
+        parse_line(&self.lines[idx])
+    }
+
+    /* All the section headers in the document as (index, level, title). */
+    pub fn section_titles(&self) -> Vec<(usize, usize, &str)> {
+        let mut v = Vec::new();
+
+        for (i, l) in self.lines.iter().enumerate() {
+            if let Line::Header { level, title } = parse_line(l) {
+                v.push((i, level, title));
+            }
+        }
+
+        v
+    }
+
+    /*
+     * Get the range of lines for the first section whose header contains the
+     * given text (case insensitive). The text is a regular expression, as it
+     * always has been for `mdtodo -s`, and one that's invalid matches no
+     * section. The section ends at the next header of the same level. An
+     * empty section name selects the whole document.
+     */
+    pub fn section(&self, section: &str) -> Option<Range<usize>> {
+        if section.is_empty() {
+            return Some(0..self.lines.len());
+        }
+
+        let hdr = format!(r"^(#+) .*(?i){}(?-i).*$", section);
+        let section_hdr = Regex::new(&hdr).ok()?;
+
+        let (start, sec_level) = self.lines.iter().enumerate().find_map(|(i, l)| {
+            section_hdr
+                .captures(l)
+                .map(|caps| (i, caps.get(1).unwrap().as_str().len()))
+        })?;
+
+        let end = self.lines[(start + 1)..]
+            .iter()
+            .position(|l| match parse_line(l) {
+                Line::Header { level, .. } => level == sec_level,
+                _ => false,
+            })
+            .map_or(self.lines.len(), |p| start + 1 + p);
+
+        Some(start..end)
+    }
+
+    /*
+     * Return true if the line is a task with the given tag (case insensitive).
+     * Completed tasks only match if requested.
+     */
+    pub fn has_tag(&self, idx: usize, tag: &str, show_completed: bool) -> bool {
+        match self.line(idx) {
+            Line::Task { done, text, .. } => {
+                (!done || show_completed)
+                    && text
+                        .split(' ')
+                        .skip(1)
+                        .any(|w| is_tag(w) && w[1..].eq_ignore_ascii_case(tag))
+            }
+            _ => false,
+        }
+    }
+
+    /* Add a new task to the top of the document. */
+    pub fn add_task(&mut self, task: &str) {
+        let idx = self.lines.len().min(4); /* skip over Obsidian Metadata */
+        self.lines.insert(idx, format!("- [ ] {}", task));
+    }
+
+    /* Toggle a task's completion status. */
+    pub fn toggle_task(&mut self, idx: usize) -> Result<()> {
+        if idx >= self.lines.len() {
+            return Err(format!("invalid task number ({})", idx + 1).into());
+        }
+
+        match self.line(idx) {
+            Line::Task { .. } => {
+                self.lines[idx] = toggle_task(&self.lines[idx]);
+                Ok(())
+            }
+            _ => Err(format!("line {} is not a task", idx + 1).into()),
+        }
+    }
+}